@@ -0,0 +1,45 @@
+//! Contention benchmark of `Stack` with and without the elimination array.
+//!
+//! Run with `cargo bench --bench stack`.
+
+use std::thread::{available_parallelism, scope};
+use std::time::{Duration, Instant};
+
+use cs431::lockfree::Stack;
+
+/// Number of push-pop pairs per thread.
+const OPS: usize = 100_000;
+
+/// Runs push-pop pairs on `stack` from the given number of threads, and returns the elapsed time.
+fn run(stack: &Stack<usize>, threads: usize) -> Duration {
+    let start = Instant::now();
+    scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                for i in 0..OPS {
+                    stack.push(i);
+                    let _ = stack.pop();
+                }
+            });
+        }
+    });
+    start.elapsed()
+}
+
+fn main() {
+    let max_threads = available_parallelism().map_or(4, |n| n.get()) * 2;
+
+    println!("threads  treiber (Mops/s)  elimination (Mops/s)");
+    let mut threads = 1;
+    while threads <= max_threads {
+        let total = (2 * OPS * threads) as f64;
+        let treiber = run(&Stack::new(), threads);
+        let elimination = run(&Stack::with_elimination(), threads);
+        println!(
+            "{threads:>7}  {:>16.2}  {:>20.2}",
+            total / treiber.as_secs_f64() / 1e6,
+            total / elimination.as_secs_f64() / 1e6,
+        );
+        threads *= 2;
+    }
+}
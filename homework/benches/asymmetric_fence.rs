@@ -0,0 +1,75 @@
+//! Read-heavy traversal of a hazard pointer protected list, to compare symmetric and asymmetric
+//! fences.
+//!
+//! Each thread mostly looks up random keys in a list set, which protects every node on the way,
+//! and occasionally removes and re-inserts a key, which retires nodes and triggers collections.
+//!
+//! Run with `cargo bench --bench asymmetric_fence`, and then with
+//! `cargo bench --bench asymmetric_fence --features asymmetric-fence`.
+
+use std::thread::{available_parallelism, scope};
+use std::time::{Duration, Instant};
+
+use cs431_homework::hazard_pointer::ListSet;
+use cs431_homework::ConcurrentSet;
+use rand::prelude::*;
+
+/// Number of operations per thread.
+const OPS: usize = 100_000;
+
+/// Number of keys in the list.
+const KEYS: usize = 256;
+
+/// Percentage of the operations that update the list.
+const UPDATE_PERCENT: u32 = 2;
+
+/// Runs the workload and returns the elapsed time.
+fn run(threads: usize) -> Duration {
+    let set = ListSet::new();
+    for key in 0..KEYS {
+        assert!(set.insert(key));
+    }
+
+    let start = Instant::now();
+    scope(|scope| {
+        for _ in 0..threads {
+            let _unused = scope.spawn(|| {
+                let mut rng = thread_rng();
+                for _ in 0..OPS {
+                    let key = rng.gen_range(0..KEYS);
+                    if rng.gen_range(0..100) < UPDATE_PERCENT {
+                        if set.remove(&key) {
+                            let _ = set.insert(key);
+                        }
+                    } else {
+                        let _ = set.contains(&key);
+                    }
+                }
+            });
+        }
+    });
+    start.elapsed()
+}
+
+fn main() {
+    let max_threads = available_parallelism().map_or(4, |n| n.get());
+
+    println!(
+        "fences: {}",
+        if cfg!(feature = "asymmetric-fence") {
+            "asymmetric"
+        } else {
+            "symmetric"
+        }
+    );
+    println!("threads  throughput (Mops/s)");
+    let mut threads = 1;
+    while threads <= max_threads {
+        let elapsed = run(threads);
+        println!(
+            "{threads:>7}  {:>19.2}",
+            (OPS * threads) as f64 / elapsed.as_secs_f64() / 1e6,
+        );
+        threads *= 2;
+    }
+}
@@ -0,0 +1,136 @@
+//! Protection cost of hazard pointers and hazard eras.
+//!
+//! Each thread repeatedly protects and reads all the cells of a shared array, and occasionally
+//! replaces a cell and retires the old value. Hazard pointers validate every cell with a fence,
+//! while hazard eras publish a new era only when the clock has advanced.
+//!
+//! Run with `cargo bench --bench protect`.
+
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::thread::{available_parallelism, scope};
+use std::time::{Duration, Instant};
+
+use cs431_homework::{hazard_era, hazard_pointer};
+use rand::prelude::*;
+
+/// Number of traversals per thread.
+const OPS: usize = 20_000;
+
+/// Number of cells in the array.
+const CELLS: usize = 64;
+
+/// Percentage of the traversals followed by a replacement.
+const UPDATE_PERCENT: u32 = 10;
+
+/// A reclamation scheme with the `Shield`/`retire` shape.
+trait Scheme {
+    const NAME: &'static str;
+    type Shield: Default;
+
+    fn alloc<T>(data: T) -> *mut T;
+    fn protect<T>(shield: &Self::Shield, src: &AtomicPtr<T>) -> *mut T;
+    fn clear(shield: &Self::Shield);
+    unsafe fn retire<T: Send>(pointer: *mut T);
+}
+
+struct HazardPointers;
+
+impl Scheme for HazardPointers {
+    const NAME: &'static str = "hazard pointers";
+    type Shield = hazard_pointer::Shield<'static>;
+
+    fn alloc<T>(data: T) -> *mut T {
+        Box::into_raw(Box::new(data))
+    }
+
+    fn protect<T>(shield: &Self::Shield, src: &AtomicPtr<T>) -> *mut T {
+        shield.protect(src)
+    }
+
+    fn clear(shield: &Self::Shield) {
+        shield.clear()
+    }
+
+    unsafe fn retire<T: Send>(pointer: *mut T) {
+        hazard_pointer::retire(pointer)
+    }
+}
+
+struct HazardEras;
+
+impl Scheme for HazardEras {
+    const NAME: &'static str = "hazard eras";
+    type Shield = hazard_era::Shield<'static>;
+
+    fn alloc<T>(data: T) -> *mut T {
+        hazard_era::alloc(data)
+    }
+
+    fn protect<T>(shield: &Self::Shield, src: &AtomicPtr<T>) -> *mut T {
+        shield.protect(src)
+    }
+
+    fn clear(shield: &Self::Shield) {
+        shield.clear()
+    }
+
+    unsafe fn retire<T: Send>(pointer: *mut T) {
+        hazard_era::retire(pointer)
+    }
+}
+
+/// Runs the workload with the scheme `S`, and returns the elapsed time.
+fn run<S: Scheme>(threads: usize) -> Duration {
+    let cells = (0..CELLS)
+        .map(|i| AtomicPtr::new(S::alloc(i)))
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+    scope(|scope| {
+        for _ in 0..threads {
+            let _unused = scope.spawn(|| {
+                let mut rng = thread_rng();
+                // One shield per cell, as a shield of either scheme protects only its latest pointer.
+                let shields = (0..CELLS).map(|_| S::Shield::default()).collect::<Vec<_>>();
+                let mut sum = 0;
+                for _ in 0..OPS {
+                    for (cell, shield) in cells.iter().zip(&shields) {
+                        sum += unsafe { *S::protect(shield, cell) };
+                    }
+                    shields.iter().for_each(S::clear);
+
+                    if rng.gen_range(0..100) < UPDATE_PERCENT {
+                        let cell = &cells[rng.gen_range(0..CELLS)];
+                        let old = cell.swap(S::alloc(sum), Ordering::AcqRel);
+                        unsafe { S::retire(old) };
+                    }
+                }
+            });
+        }
+    });
+    let elapsed = start.elapsed();
+
+    for cell in cells {
+        unsafe { S::retire(cell.into_inner()) };
+    }
+    elapsed
+}
+
+fn main() {
+    let max_threads = available_parallelism().map_or(4, |n| n.get());
+
+    println!("scheme           threads  throughput (Mprotects/s)");
+    let mut threads = 1;
+    while threads <= max_threads {
+        for (name, elapsed) in [
+            (HazardPointers::NAME, run::<HazardPointers>(threads)),
+            (HazardEras::NAME, run::<HazardEras>(threads)),
+        ] {
+            println!(
+                "{name:<15}  {threads:>7}  {:>24.2}",
+                (OPS * CELLS * threads) as f64 / elapsed.as_secs_f64() / 1e6,
+            );
+        }
+        threads *= 2;
+    }
+}
@@ -0,0 +1,92 @@
+//! Memory and throughput of `RetiredSet` for different reclamation thresholds.
+//!
+//! Each thread repeatedly replaces the value of a shared counter and retires the old one, while
+//! holding `SHIELDS` idle shields so that the number of hazard slots grows with the threads.
+//!
+//! Run with `cargo bench --bench retired_set`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::thread::{available_parallelism, scope};
+use std::time::{Duration, Instant};
+
+use cs431_homework::hazard_pointer::{RetiredSet, Shield, HAZARDS};
+
+/// Number of replacements per thread.
+const OPS: usize = 100_000;
+
+/// Number of idle shields per thread.
+const SHIELDS: usize = 4;
+
+/// Factors `k` of the reclamation threshold `R = k * H` to compare.
+const FACTORS: [usize; 4] = [1, 2, 4, 8];
+
+/// Allocator that tracks the peak number of live bytes.
+struct Counting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let live = LIVE.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        let _ = PEAK.fetch_max(live, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ = LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOC: Counting = Counting;
+
+/// Runs the workload with the given factor, and returns the elapsed time and the peak number of
+/// live bytes.
+fn run(threads: usize, factor: usize) -> (Duration, usize) {
+    let counter = AtomicPtr::new(Box::into_raw(Box::new([0usize; 4])));
+    PEAK.store(LIVE.load(Ordering::Relaxed), Ordering::Relaxed);
+
+    let start = Instant::now();
+    scope(|scope| {
+        for _ in 0..threads {
+            let _unused = scope.spawn(|| {
+                let _idle = (0..SHIELDS).map(|_| Shield::default()).collect::<Vec<_>>();
+                let mut retired = RetiredSet::builder(&HAZARDS).factor(factor).build();
+                let shield = Shield::default();
+                for _ in 0..OPS {
+                    let old = shield.protect(&counter);
+                    let new = Box::into_raw(Box::new([unsafe { (*old)[0] } + 1; 4]));
+                    match counter.compare_exchange(old, new, Ordering::AcqRel, Ordering::Acquire) {
+                        Ok(_) => unsafe { retired.retire(old) },
+                        Err(_) => drop(unsafe { Box::from_raw(new) }),
+                    }
+                }
+            });
+        }
+    });
+    let elapsed = start.elapsed();
+
+    drop(unsafe { Box::from_raw(counter.into_inner()) });
+    (elapsed, PEAK.load(Ordering::Relaxed))
+}
+
+fn main() {
+    let max_threads = available_parallelism().map_or(4, |n| n.get());
+
+    println!("threads  factor  throughput (Mops/s)  peak memory (KiB)");
+    let mut threads = 1;
+    while threads <= max_threads {
+        for factor in FACTORS {
+            let (elapsed, peak) = run(threads, factor);
+            println!(
+                "{threads:>7}  {factor:>6}  {:>19.2}  {:>17}",
+                (OPS * threads) as f64 / elapsed.as_secs_f64() / 1e6,
+                peak / 1024,
+            );
+        }
+        threads *= 2;
+    }
+}
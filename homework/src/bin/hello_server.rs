@@ -1,4 +1,5 @@
-use crossbeam_channel::{bounded, unbounded};
+use crossbeam_channel::bounded;
+use cs431::lockfree::channel::unbounded;
 use cs431_homework::hello_server::{CancellableTcpListener, Handler, Statistics, ThreadPool};
 use std::io;
 use std::sync::Arc;
@@ -5,7 +5,7 @@ use core::marker::PhantomData;
 use core::mem;
 use core::ops::{Deref, DerefMut};
 use core::sync::atomic::{AtomicUsize, Ordering};
-use crossbeam_epoch::{Atomic, Guard, Owned, Pointer, Shared};
+use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Pointer, Shared};
 
 /// Growable array of `Atomic<T>`.
 ///
@@ -129,6 +129,7 @@ use crossbeam_epoch::{Atomic, Guard, Owned, Pointer, Shared};
 /// example in `SplitOrderedList`, destruction of elements are handled by `List`.
 #[derive(Debug)]
 pub struct GrowableArray<T> {
+    /// The root segment, tagged with the height of the tree.
     root: Atomic<Segment>,
     _marker: PhantomData<T>,
 }
@@ -171,6 +172,24 @@ impl Debug for Segment {
     }
 }
 
+/// Deallocates the segment tree of the given height, but not the elements. A tree of height 0 is
+/// an element.
+///
+/// # Safety
+///
+/// `pointer` must be null or a segment tree of the given height owned by the caller.
+unsafe fn free_segments(pointer: usize, height: usize) {
+    if pointer == 0 || height == 0 {
+        return;
+    }
+    let segment = Box::from_raw(pointer as *mut Segment);
+    if height > 1 {
+        for child in segment.iter() {
+            free_segments(child.load(Ordering::Relaxed), height - 1);
+        }
+    }
+}
+
 impl<T> Drop for GrowableArray<T> {
     /// Deallocate segments, but not the individual elements.
     fn drop(&mut self) {
@@ -193,9 +212,220 @@ impl<T> GrowableArray<T> {
         }
     }
 
+    /// Releases the segments that only hold the indices not less than `len`, and lowers the height
+    /// of the tree if `len` fits in a lower tree.
+    ///
+    /// The elements at the released indices are not dropped. They must have been removed by the
+    /// container beforehand.
+    pub fn truncate(&mut self, len: usize) {
+        // SAFETY: `&mut self` guarantees that no other thread accesses the array.
+        let guard = unsafe { unprotected() };
+        let mut root = self.root.load(Ordering::Relaxed, guard);
+        let mut height = root.tag();
+
+        // Replace the root with its first child while the indices fit in it.
+        while !root.is_null() && height > 1 && len <= 1 << (SEGMENT_LOGSIZE * (height - 1)) {
+            // SAFETY: we own the segments.
+            let segment = unsafe { root.into_owned() };
+            for child in &segment[1..] {
+                unsafe { free_segments(child.load(Ordering::Relaxed), height - 1) };
+            }
+            height -= 1;
+            let first = segment[0].load(Ordering::Relaxed);
+            if first == 0 {
+                // The tree is empty.
+                height = 0;
+            }
+            root = unsafe { Shared::from_usize(first) }.with_tag(height);
+        }
+
+        if len == 0 {
+            unsafe { free_segments(root.with_tag(0).into_usize(), height) };
+            root = Shared::null();
+        } else if !root.is_null() {
+            // Release the children after the one holding the index `len - 1`, at each level.
+            let mut segment = unsafe { root.deref() };
+            let mut level = height;
+            let last = len - 1;
+            loop {
+                let shift = SEGMENT_LOGSIZE * (level - 1);
+                let index = (last >> shift) & ((1 << SEGMENT_LOGSIZE) - 1);
+                for child in &segment[index + 1..] {
+                    unsafe { free_segments(child.swap(0, Ordering::Relaxed), level - 1) };
+                }
+                if level == 1 {
+                    break;
+                }
+                let child = segment[index].load(Ordering::Relaxed);
+                if child == 0 {
+                    break;
+                }
+                segment = unsafe { &*(child as *const Segment) };
+                level -= 1;
+            }
+        }
+
+        self.root.store(root, Ordering::Relaxed);
+    }
+
+    /// Releases the segments that only hold the indices not less than `len`, while the array may be
+    /// accessed by the other threads. Unlike `truncate`, the height of the tree is kept, and the
+    /// slots of the remaining segments are left as they are.
+    ///
+    /// The released segments are freed after the threads that are pinned now unpin, so the slots
+    /// returned by `get` stay valid while pinned. But a store to a released slot may be lost, and
+    /// a concurrent `get` of a released index allocates the segments again.
+    ///
+    /// The elements at the released indices are not dropped. They must have been removed by the
+    /// container beforehand.
+    pub fn release(&self, len: usize, guard: &Guard) {
+        let root = self.root.load(Ordering::Acquire, guard);
+        let height = root.tag();
+        if root.is_null() {
+            return;
+        }
+
+        if len == 0 {
+            if self
+                .root
+                .compare_exchange(
+                    root,
+                    Shared::null(),
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                    guard,
+                )
+                .is_ok()
+            {
+                let root = root.with_tag(0).into_usize();
+                // SAFETY: the tree is detached, and the threads that may still access it are
+                // pinned.
+                unsafe { guard.defer_unchecked(move || free_segments(root, height)) };
+            }
+            return;
+        }
+
+        let last = len - 1;
+        if SEGMENT_LOGSIZE * height < usize::BITS as usize
+            && last >> (SEGMENT_LOGSIZE * height) != 0
+        {
+            // `len` exceeds the capacity, so nothing to release.
+            return;
+        }
+
+        // Release the children after the one holding the index `len - 1`, at each level above the
+        // leaves.
+        // SAFETY: the segments reachable from the root are freed only after we unpin.
+        let mut segment = unsafe { root.deref() };
+        let mut level = height;
+        while level > 1 {
+            let shift = SEGMENT_LOGSIZE * (level - 1);
+            let index = (last >> shift) & ((1 << SEGMENT_LOGSIZE) - 1);
+            for child in &segment[index + 1..] {
+                let child = child.swap(0, Ordering::AcqRel);
+                if child != 0 {
+                    // SAFETY: the subtree is detached, and the threads that may still access it
+                    // are pinned.
+                    unsafe { guard.defer_unchecked(move || free_segments(child, level - 1)) };
+                }
+            }
+            let child = segment[index].load(Ordering::Acquire);
+            if child == 0 {
+                break;
+            }
+            // SAFETY: the same as for the root.
+            segment = unsafe { &*(child as *const Segment) };
+            level -= 1;
+        }
+    }
+
+    /// Returns the height of the segment tree. The height is 0 if no segment is allocated yet.
+    pub fn height(&self) -> usize {
+        // SAFETY: only the tag is read, and the root is not dereferenced.
+        let guard = unsafe { unprotected() };
+        self.root.load(Ordering::Acquire, guard).tag()
+    }
+
+    /// Returns the number of indices that the current segment tree can hold, i.e. the indices
+    /// below the capacity are accessed with `get` without growing the tree.
+    ///
+    /// The capacity saturates at `usize::MAX`: if the tree can hold more than `usize::MAX`
+    /// indices, i.e. all of them, `usize::MAX` is returned even though the index `usize::MAX` is
+    /// also held.
+    pub fn capacity(&self) -> usize {
+        match self.height() {
+            0 => 0,
+            height => u32::try_from(SEGMENT_LOGSIZE * height)
+                .ok()
+                .and_then(|shift| 1usize.checked_shl(shift))
+                .unwrap_or(usize::MAX),
+        }
+    }
+
+    /// Returns an iterator over the non-null slots in the ascending order of their indices, along
+    /// with the indices.
+    ///
+    /// The iterator does not allocate segments. A slot is yielded if it is non-null when visited,
+    /// so the slots that are concurrently written may or may not be yielded.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, T> {
+        let root = self.root.load(Ordering::Acquire, guard);
+        Iter {
+            // SAFETY: segments are freed only by `truncate` and `drop`, which take `&mut self`, or
+            // by `release` after the guard is unpinned.
+            stack: unsafe { root.as_ref() }
+                .map(|root| (root, 0))
+                .into_iter()
+                .collect(),
+            height: root.tag(),
+            _marker: PhantomData,
+        }
+    }
+
     /// Returns the reference to the `Atomic` pointer at `index`. Allocates new segments if
     /// necessary.
     pub fn get(&self, mut index: usize, guard: &Guard) -> &Atomic<T> {
         todo!()
     }
 }
+
+/// Iterator over the non-null slots of a [`GrowableArray`], returned by [`GrowableArray::iter`].
+#[derive(Debug)]
+pub struct Iter<'g, T> {
+    /// The segments from the root to the segment being visited, with the index of the next child
+    /// to visit in each of them.
+    stack: Vec<(&'g Segment, usize)>,
+    /// The height of the tree when the iterator is created.
+    height: usize,
+    _marker: PhantomData<&'g Atomic<T>>,
+}
+
+impl<'g, T> Iterator for Iter<'g, T> {
+    type Item = (usize, &'g Atomic<T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((segment, next)) = self.stack.last_mut() {
+            let segment = *segment;
+            let Some(child) = segment.get(*next) else {
+                let _ = self.stack.pop();
+                continue;
+            };
+            *next += 1;
+
+            if self.stack.len() < self.height {
+                let child = child.load(Ordering::Acquire);
+                if child != 0 {
+                    // SAFETY: segments are not freed while the array is borrowed and the guard is pinned.
+                    self.stack.push((unsafe { &*(child as *const Segment) }, 0));
+                }
+            } else if child.load(Ordering::Acquire) != 0 {
+                let index = self.stack.iter().fold(0, |index, (_, next)| {
+                    (index << SEGMENT_LOGSIZE) | (next - 1)
+                });
+                // SAFETY: the slots of the leaf segments are `Atomic<T>`.
+                let slot = unsafe { &*(child as *const AtomicUsize).cast::<Atomic<T>>() };
+                return Some((index, slot));
+            }
+        }
+        None
+    }
+}
@@ -3,5 +3,5 @@
 mod growable_array;
 mod split_ordered_list;
 
-pub use growable_array::GrowableArray;
-pub use split_ordered_list::SplitOrderedList;
+pub use growable_array::{GrowableArray, Iter as GrowableArrayIter};
+pub use split_ordered_list::{Iter as SplitOrderedListIter, SplitOrderedList};
@@ -1,51 +1,322 @@
 //! Split-ordered linked list.
 
+use core::cmp::Ordering::{Equal, Less};
+use core::hash::{BuildHasher, Hash};
 use core::mem;
 use core::sync::atomic::{AtomicUsize, Ordering};
-use crossbeam_epoch::{Guard, Owned};
-use cs431::lockfree::list::{Cursor, List, Node};
+use crossbeam_epoch::{Atomic, Guard, Owned, Shared};
+use cs431::lockfree::list::{self, Cursor, List, Node};
+use std::collections::hash_map::RandomState;
 
 use super::growable_array::GrowableArray;
 use crate::NonblockingMap;
 
-/// Lock-free map from `usize` in range [0, 2^63-1] to `V`.
-///
-/// NOTE: We don't care about hashing in this homework for simplicity.
+/// The most significant bit, which is reserved to tell the regular nodes from the sentinels.
+const MSB: usize = 1 << (usize::BITS - 1);
+
+/// The tag of the null pointer in a bucket whose sentinel is being unlinked by `shrink_if_sparse`.
+const UNLINKING: usize = 1;
+
+/// A node of the list, which is either a sentinel or a regular node.
+type SplitNode<K, V> = Node<SplitKey<K>, Option<V>>;
+
+/// Lock-free hash map from `K` to `V`, whose hashes are built with `S`.
 #[derive(Debug)]
-pub struct SplitOrderedList<V> {
+pub struct SplitOrderedList<K, V, S = RandomState> {
     /// Lock-free list sorted by recursive-split order. Use `None` sentinel node value.
-    list: List<usize, Option<V>>,
+    list: List<SplitKey<K>, Option<V>>,
     /// array of pointers to the buckets
-    buckets: GrowableArray<Node<usize, Option<V>>>,
+    buckets: GrowableArray<Node<SplitKey<K>, Option<V>>>,
     /// number of buckets
     size: AtomicUsize,
     /// number of items
     count: AtomicUsize,
+    /// builder of the hashes of the keys
+    hasher: S,
 }
 
-impl<V> Default for SplitOrderedList<V> {
-    fn default() -> Self {
+/// The key of a node in the list: the split-order hash, and the full key for the regular nodes.
+///
+/// The keys are ordered by `hash` only. Distinct keys with the same hash (hash collisions) are
+/// adjacent in the list, in no particular order, and are told apart with `K: Eq` by
+/// [`SplitOrderedList::seek`].
+#[derive(Debug)]
+struct SplitKey<K> {
+    /// The bit-reversed hash. Its least significant bit is set for the regular nodes only.
+    hash: usize,
+    /// `None` for the sentinels.
+    key: Option<K>,
+}
+
+impl<K> SplitKey<K> {
+    /// The key of the sentinel node of the bucket at `index`.
+    fn sentinel(index: usize) -> Self {
         Self {
-            list: List::new(),
-            buckets: GrowableArray::new(),
-            size: AtomicUsize::new(2),
-            count: AtomicUsize::new(0),
+            hash: index.reverse_bits(),
+            key: None,
         }
     }
 }
 
-impl<V> SplitOrderedList<V> {
-    /// `size` is doubled when `count > size * LOAD_FACTOR`.
-    const LOAD_FACTOR: usize = 2;
+impl<K> PartialEq for SplitKey<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+    }
+}
+
+impl<K> Eq for SplitKey<K> {}
+
+impl<K> PartialOrd for SplitKey<K> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K> Ord for SplitKey<K> {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.hash.cmp(&other.hash)
+    }
+}
+
+/// Iterator over the entries of a [`SplitOrderedList`], returned by [`SplitOrderedList::iter`].
+#[derive(Debug)]
+pub struct Iter<'g, K, V> {
+    inner: list::Iter<'g, SplitKey<K>, Option<V>>,
+}
 
+impl<'g, K, V> Iterator for Iter<'g, K, V> {
+    type Item = (&'g K, &'g V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // skip the sentinels
+        self.inner
+            .by_ref()
+            .find_map(|(key, value)| Some((key.key.as_ref()?, value.as_ref()?)))
+    }
+}
+
+impl<K, V, S: Default> Default for SplitOrderedList<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K, V> SplitOrderedList<K, V> {
     /// Creates a new split ordered list.
     pub fn new() -> Self {
         Self::default()
     }
+}
+
+impl<K, V, S> SplitOrderedList<K, V, S> {
+    /// `size` is doubled when `count > size * LOAD_FACTOR`.
+    const LOAD_FACTOR: usize = 2;
+
+    /// `size` is halved when `count * SHRINK_FACTOR < size`. Together with `LOAD_FACTOR`, the
+    /// load factor has to change 4 times to resize back, so the table does not oscillate.
+    const SHRINK_FACTOR: usize = 2;
+
+    /// The initial and minimum number of buckets.
+    const MIN_SIZE: usize = 2;
+
+    /// Creates a new split ordered list which uses `hasher` to hash the keys.
+    pub fn with_hasher(hasher: S) -> Self {
+        Self {
+            list: List::new(),
+            buckets: GrowableArray::new(),
+            size: AtomicUsize::new(Self::MIN_SIZE),
+            count: AtomicUsize::new(0),
+            hasher,
+        }
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over the entries.
+    ///
+    /// The entries are yielded in the split order, i.e. by their bit-reversed hashes, and not in
+    /// the order of the keys. The iterator is weakly consistent in the same way as
+    /// [`List::iter`]: an entry that is present during the whole iteration is yielded exactly
+    /// once, and an entry that is concurrently inserted or deleted may or may not be yielded.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, K, V> {
+        Iter {
+            inner: self.list.iter(guard),
+        }
+    }
 
     /// Creates a cursor and moves it to the bucket for the given index.  If the bucket doesn't
     /// exist, recursively initializes the buckets.
-    fn lookup_bucket<'s>(&'s self, index: usize, guard: &'s Guard) -> Cursor<'s, usize, Option<V>> {
+    ///
+    /// Use `load_bucket` to read a bucket and `init_bucket` to initialize it, which implement the
+    /// protocol with `shrink_if_sparse`. If the bucket is being unlinked, or its initialization
+    /// fails, retry from the bucket or use the cursor of its parent bucket, which precedes it in
+    /// the split order.
+    fn lookup_bucket<'s>(
+        &'s self,
+        index: usize,
+        guard: &'s Guard,
+    ) -> Cursor<'s, SplitKey<K>, Option<V>> {
+        todo!()
+    }
+
+    /// Returns the sentinel of `bucket`, or null if the bucket is not initialized. The cursor at
+    /// the sentinel is `Cursor::new(bucket, sentinel)`.
+    ///
+    /// Returns `Err(())` if the sentinel of the bucket is being unlinked by `shrink_if_sparse`.
+    /// Then the bucket must not be initialized until the unlinking is done, and the cursor of the
+    /// parent bucket should be used instead.
+    fn load_bucket<'s>(
+        bucket: &'s Atomic<SplitNode<K, V>>,
+        guard: &'s Guard,
+    ) -> Result<Shared<'s, SplitNode<K, V>>, ()> {
+        let sentinel = bucket.load(Ordering::Acquire, guard);
+        if sentinel.tag() == UNLINKING {
+            return Err(());
+        }
+        Ok(sentinel)
+    }
+
+    /// Initializes `bucket` at `index` by inserting its sentinel to `list` from `parent`, the
+    /// cursor of the parent bucket, and returns `parent` moved to the sentinel.
+    ///
+    /// The sentinel is stored in the bucket only by the thread that inserted it to the list. If
+    /// the sentinel is already in the list, the cursor at it is returned without storing it, since
+    /// it may be unlinked in the meantime. If the bucket is being unlinked when the inserted
+    /// sentinel is stored, the sentinel is deleted from the list again. Hence, a deleted sentinel
+    /// is never stored in a bucket.
+    ///
+    /// Returns `Err(())` if the list is concurrently modified or the bucket is being unlinked.
+    fn init_bucket<'s>(
+        list: &'s List<SplitKey<K>, Option<V>>,
+        bucket: &'s Atomic<SplitNode<K, V>>,
+        index: usize,
+        mut parent: Cursor<'s, SplitKey<K>, Option<V>>,
+        guard: &'s Guard,
+    ) -> Result<Cursor<'s, SplitKey<K>, Option<V>>, ()> {
+        let key = SplitKey::sentinel(index);
+        if !parent.find_harris_michael(&key, guard)? {
+            parent
+                .insert(Owned::new(Node::new(key, None)), guard)
+                .map_err(|_| ())?;
+            if bucket
+                .compare_exchange(
+                    Shared::null(),
+                    parent.curr(),
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                )
+                .is_err()
+            {
+                let _ = list.harris_michael_delete(&SplitKey::sentinel(index), guard);
+                return Err(());
+            }
+        }
+        Ok(parent)
+    }
+
+    /// Unlinks the sentinel of `bucket` at `index` from `list`, if the bucket is initialized.
+    ///
+    /// The bucket is first marked with `UNLINKING`, so that the cursors from `load_bucket` fail to
+    /// validate and no thread initializes it again while its sentinel is deleted from the list.
+    /// Then it is reset to null. See `init_bucket` for why a deleted sentinel is never stored in a
+    /// bucket again. The unlinked sentinel is freed after the threads that may still use it unpin.
+    fn unlink_bucket(
+        list: &List<SplitKey<K>, Option<V>>,
+        bucket: &Atomic<SplitNode<K, V>>,
+        index: usize,
+        guard: &Guard,
+    ) {
+        let sentinel = bucket.load(Ordering::Acquire, guard);
+        if sentinel.is_null()
+            || bucket
+                .compare_exchange(
+                    sentinel,
+                    Shared::null().with_tag(UNLINKING),
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                    guard,
+                )
+                .is_err()
+        {
+            return;
+        }
+        let _ = list.harris_michael_delete(&SplitKey::sentinel(index), guard);
+        bucket.store(Shared::null(), Ordering::Release);
+    }
+
+    /// Halves `size` if the table is sparse. Called by `delete` after it removes an entry.
+    ///
+    /// The thread that halves `size` unlinks the buckets beyond the new size.
+    fn shrink_if_sparse(&self, guard: &Guard) {
+        let size = self.size.load(Ordering::Acquire);
+        let count = self.count.load(Ordering::Relaxed);
+        if size > Self::MIN_SIZE
+            && count.saturating_mul(Self::SHRINK_FACTOR) < size
+            && self
+                .size
+                .compare_exchange(size, size / 2, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.unlink_buckets(size / 2, guard);
+        }
+    }
+
+    /// Unlinks the sentinels of the buckets from `from`, and releases the segments of the bucket
+    /// array that only hold them. See `unlink_bucket`. The released segments are freed after the
+    /// threads that may still use them unpin.
+    ///
+    /// A thread that read the old `size` may initialize a bucket beyond the new size concurrently.
+    /// Its sentinel is harmless in the list, as the lookups start from the parent buckets that
+    /// precede it in the split order. It is unlinked by a later shrink, unless it was stored to a
+    /// segment released in the meantime.
+    fn unlink_buckets(&self, from: usize, guard: &Guard) {
+        for (index, bucket) in self.buckets.iter(guard).filter(|(index, _)| *index >= from) {
+            Self::unlink_bucket(&self.list, bucket, index, guard);
+        }
+        self.buckets.release(from, guard);
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> SplitOrderedList<K, V, S> {
+    /// Returns the hash of `key`, which indexes the buckets. The most significant bit is cleared,
+    /// as it is reserved for `SplitKey`.
+    fn hash(&self, key: &K) -> usize {
+        self.hasher.hash_one(key) as usize & !MSB
+    }
+
+    /// Returns the key of the regular node of `key` with the given `hash`.
+    fn split_key(hash: usize, key: K) -> SplitKey<K> {
+        SplitKey {
+            hash: (hash | MSB).reverse_bits(),
+            key: Some(key),
+        }
+    }
+
+    /// Returns the comparator for `Cursor::find_harris_michael_by` that finds the node of `key`
+    /// with the given `hash`.
+    ///
+    /// The other keys with the same hash compare less, so that the cursor moves past them. If
+    /// `key` is not found, the cursor stops at the end of the hash collisions, where a new node of
+    /// `key` should be inserted.
+    fn seek(hash: usize, key: &K) -> impl Fn(&SplitKey<K>) -> core::cmp::Ordering + '_ {
+        let hash = (hash | MSB).reverse_bits();
+        move |node| match node.hash.cmp(&hash) {
+            Equal if node.key.as_ref() != Some(key) => Less,
+            ordering => ordering,
+        }
+    }
+
+    /// Removes the entry of `key`, and decrements `count` if it is removed.
+    fn remove<'s>(&'s self, key: &K, guard: &'s Guard) -> Result<&'s V, ()> {
         todo!()
     }
 
@@ -53,30 +324,111 @@ impl<V> SplitOrderedList<V> {
     /// Returns `(size, found, cursor)`
     fn find<'s>(
         &'s self,
-        key: &usize,
+        key: &K,
         guard: &'s Guard,
-    ) -> (usize, bool, Cursor<'s, usize, Option<V>>) {
+    ) -> (usize, bool, Cursor<'s, SplitKey<K>, Option<V>>) {
         todo!()
     }
-
-    fn assert_valid_key(key: usize) {
-        assert!(key.leading_zeros() != 0);
-    }
 }
 
-impl<V> NonblockingMap<usize, V> for SplitOrderedList<V> {
-    fn lookup<'a>(&'a self, key: &usize, guard: &'a Guard) -> Option<&'a V> {
-        Self::assert_valid_key(*key);
+impl<K: Hash + Eq + Clone, V, S: BuildHasher> NonblockingMap<K, V> for SplitOrderedList<K, V, S> {
+    fn lookup<'a>(&'a self, key: &K, guard: &'a Guard) -> Option<&'a V> {
         todo!()
     }
 
-    fn insert(&self, key: &usize, value: V, guard: &Guard) -> Result<(), V> {
-        Self::assert_valid_key(*key);
+    fn insert(&self, key: &K, value: V, guard: &Guard) -> Result<(), V> {
         todo!()
     }
 
-    fn delete<'a>(&'a self, key: &usize, guard: &'a Guard) -> Result<&'a V, ()> {
-        Self::assert_valid_key(*key);
-        todo!()
+    fn delete<'a>(&'a self, key: &K, guard: &'a Guard) -> Result<&'a V, ()> {
+        let value = self.remove(key, guard)?;
+        self.shrink_if_sparse(guard);
+        Ok(value)
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use super::{SplitNode, SplitOrderedList, UNLINKING};
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use crossbeam_epoch::{self as epoch, Atomic, Shared};
+    use cs431::lockfree::list::{Cursor, List};
+    use std::thread;
+
+    type Map = SplitOrderedList<usize, usize>;
+
+    // a bucket is initialized by the inserter of its sentinel only, and not while being unlinked
+    #[test]
+    fn bucket_init_unlink() {
+        let list = List::new();
+        let bucket = Atomic::<SplitNode<usize, usize>>::null();
+        let guard = &epoch::pin();
+
+        assert_eq!(Map::load_bucket(&bucket, guard), Ok(Shared::null()));
+        let cursor = Map::init_bucket(&list, &bucket, 1, list.head(guard), guard).unwrap();
+        let sentinel = Map::load_bucket(&bucket, guard).unwrap();
+        assert!(!sentinel.is_null());
+        assert_eq!(cursor.curr(), sentinel);
+
+        // the sentinel found in the list is not stored
+        let other = Atomic::null();
+        let cursor = Map::init_bucket(&list, &other, 1, list.head(guard), guard).unwrap();
+        assert_eq!(cursor.curr(), sentinel);
+        assert!(other.load(Ordering::Relaxed, guard).is_null());
+
+        Map::unlink_bucket(&list, &bucket, 1, guard);
+        assert_eq!(Map::load_bucket(&bucket, guard), Ok(Shared::null()));
+        assert_eq!(list.iter(guard).count(), 0);
+
+        bucket.store(Shared::null().with_tag(UNLINKING), Ordering::Relaxed);
+        assert_eq!(Map::load_bucket(&bucket, guard), Err(()));
+        assert!(Map::init_bucket(&list, &bucket, 1, list.head(guard), guard).is_err());
+        assert_eq!(list.iter(guard).count(), 0);
+    }
+
+    // every sentinel left in the list is stored in the bucket
+    #[test]
+    fn bucket_init_unlink_concurrent() {
+        const THREADS: usize = 4;
+        const STEPS: usize = 4096;
+        let list = List::new();
+        let bucket = Atomic::null();
+        let done = AtomicBool::new(false);
+
+        thread::scope(|s| {
+            let _unlinker = s.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    Map::unlink_bucket(&list, &bucket, 1, &epoch::pin());
+                }
+            });
+            let workers = (0..THREADS)
+                .map(|_| {
+                    s.spawn(|| {
+                        let key = Map::split_key(1, 1);
+                        for _ in 0..STEPS {
+                            let guard = &epoch::pin();
+                            let head = list.head(guard);
+                            let mut cursor = match Map::load_bucket(&bucket, guard) {
+                                Ok(sentinel) if !sentinel.is_null() => {
+                                    Cursor::new(&bucket, sentinel)
+                                }
+                                Ok(_) => Map::init_bucket(&list, &bucket, 1, head, guard)
+                                    .unwrap_or_else(|()| list.head(guard)),
+                                Err(()) => head,
+                            };
+                            let _ = cursor.find_harris_michael(&key, guard);
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            for worker in workers {
+                worker.join().unwrap();
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+
+        let guard = &epoch::pin();
+        Map::unlink_bucket(&list, &bucket, 1, guard);
+        assert_eq!(list.iter(guard).count(), 0);
     }
 }
@@ -0,0 +1,243 @@
+use core::marker::PhantomData;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+use std::fmt;
+
+use super::retire::Block;
+use super::ERAS;
+
+/// The era published by an unused slot. The clock starts from `NONE + 1`.
+const NONE: usize = 0;
+
+/// Represents the ownership of a hazard era slot.
+pub struct Shield<'b> {
+    slot: NonNull<EraSlot>,
+    bag: &'b EraBag,
+    _marker: PhantomData<*mut ()>, // !Send + !Sync
+}
+
+impl<'b> Shield<'b> {
+    /// Creates a new shield for hazard era. The shield borrows `bag`, so the bag outlives it.
+    pub fn new(bag: &'b EraBag) -> Self {
+        let slot = bag.acquire_slot();
+        Self {
+            slot: slot.into(),
+            bag,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Clear the hazard era slot. The block protected by this shield is no longer protected.
+    pub fn clear(&self) {
+        // SAFETY: slots are never freed while the bag is alive, and the bag is borrowed for `'b`.
+        let slot = unsafe { self.slot.as_ref() };
+        slot.era.store(NONE, Ordering::Release);
+    }
+
+    /// Get a protected pointer from `src`.
+    ///
+    /// The pointer is protected until this shield protects another pointer, or is cleared or
+    /// dropped, as long as it is allocated with [`EraBag::alloc`] of the bag of this shield. The
+    /// pointers protected by this shield before may not be protected anymore: publishing a newer
+    /// era stops protecting the blocks retired in between. Use a shield per pointer that should
+    /// stay protected at the same time.
+    pub fn protect<T>(&self, src: &AtomicPtr<T>) -> *mut T {
+        // SAFETY: slots are never freed while the bag is alive, and the bag is borrowed for `'b`.
+        let slot = unsafe { self.slot.as_ref() };
+        let bag = self.bag;
+
+        let mut era = slot.era.load(Ordering::Relaxed);
+        loop {
+            let pointer = src.load(Ordering::Acquire);
+            let current = bag.era();
+            if current == era {
+                // `pointer` was loaded after `era` is published, so it is either born after `era`
+                // (then the clock has advanced) or not retired before `era`.
+                return pointer;
+            }
+            slot.era.store(current, Ordering::Relaxed);
+            // SeqCst: pairs with the fence in `collect()`, so that either it sees the era, or we
+            // see that the blocks retired before the era are unlinked.
+            fence(Ordering::SeqCst);
+            era = current;
+        }
+    }
+}
+
+impl Default for Shield<'static> {
+    fn default() -> Self {
+        Self::new(&ERAS)
+    }
+}
+
+impl Drop for Shield<'_> {
+    /// Clear and release the ownership of the hazard era slot.
+    fn drop(&mut self) {
+        self.clear();
+        // SAFETY: slots are never freed while the bag is alive, and the bag is borrowed by the
+        // shield.
+        let slot = unsafe { self.slot.as_ref() };
+        slot.active.store(false, Ordering::Release);
+    }
+}
+
+impl fmt::Debug for Shield<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shield")
+            .field("slot address", &self.slot)
+            .field("slot data", unsafe { self.slot.as_ref() })
+            .finish()
+    }
+}
+
+/// Global bag of hazard eras, and the era clock.
+///
+/// `EraBag.head` and `EraSlot.next` form a grow-only list of all hazard era slots. As in
+/// [`HazardBag`](crate::hazard_pointer::HazardBag), slots are never removed from this list but
+/// recycled for other `Shield`s.
+#[derive(Debug)]
+pub struct EraBag {
+    head: AtomicPtr<EraSlot>,
+    /// The global era clock, advanced on each retirement.
+    clock: AtomicUsize,
+}
+
+/// See `EraBag`
+#[derive(Debug)]
+struct EraSlot {
+    // Whether this slot is occupied by a `Shield`.
+    active: AtomicBool,
+    // The published era, or `NONE`.
+    era: AtomicUsize,
+    // Immutable pointer to the next slot in the bag.
+    next: *const EraSlot,
+}
+
+impl EraBag {
+    /// Creates a new global hazard era set.
+    pub const fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+            clock: AtomicUsize::new(NONE + 1),
+        }
+    }
+
+    /// Returns the current era.
+    pub fn era(&self) -> usize {
+        self.clock.load(Ordering::Acquire)
+    }
+
+    /// Advances the clock, and returns the era before advancing.
+    pub(crate) fn advance(&self) -> usize {
+        // SeqCst: the unlinking of the retired block happens before the new era.
+        self.clock.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// Allocates a block born in the current era.
+    pub fn alloc<T>(&self, data: T) -> *mut T {
+        Block::alloc(self.era(), data)
+    }
+
+    /// Acquires a slot in the bag, either by recycling an inactive slot or allocating a new slot.
+    fn acquire_slot(&self) -> &EraSlot {
+        if let Some(slot) = self.try_acquire_inactive() {
+            return slot;
+        }
+
+        let slot = Box::into_raw(Box::new(EraSlot {
+            active: AtomicBool::new(true),
+            era: AtomicUsize::new(NONE),
+            next: ptr::null(),
+        }));
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            // SAFETY: `slot` is not shared until the CAS succeeds.
+            unsafe { (*slot).next = head };
+            // Release: the other threads traversing the bag should see the new slot.
+            match self
+                .head
+                .compare_exchange(head, slot, Ordering::Release, Ordering::Relaxed)
+            {
+                // SAFETY: the slot is never freed while the bag is alive.
+                Ok(_) => return unsafe { &*slot },
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Find an inactive slot and activate it.
+    fn try_acquire_inactive(&self) -> Option<&EraSlot> {
+        let mut slot = self.head.load(Ordering::Acquire).cast_const();
+        // SAFETY: slots are never freed while the bag is alive, and `next` is immutable.
+        while let Some(s) = unsafe { slot.as_ref() } {
+            if s.active
+                .compare_exchange(false, true, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+            {
+                return Some(s);
+            }
+            slot = s.next;
+        }
+        None
+    }
+
+    /// Returns all the published eras in ascending order.
+    pub fn all_eras(&self) -> Vec<usize> {
+        let mut eras = Vec::new();
+        let mut slot = self.head.load(Ordering::Acquire).cast_const();
+        // SAFETY: slots are never freed while the bag is alive, and `next` is immutable.
+        while let Some(s) = unsafe { slot.as_ref() } {
+            let era = s.era.load(Ordering::Acquire);
+            if era != NONE {
+                eras.push(era);
+            }
+            slot = s.next;
+        }
+        eras.sort_unstable();
+        eras
+    }
+}
+
+impl Drop for EraBag {
+    /// Frees all slots.
+    fn drop(&mut self) {
+        let mut slot = *self.head.get_mut();
+        while !slot.is_null() {
+            // SAFETY: the slots are owned by the bag.
+            let s = unsafe { Box::from_raw(slot) };
+            slot = s.next.cast_mut();
+        }
+    }
+}
+
+unsafe impl Send for EraSlot {}
+unsafe impl Sync for EraSlot {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Block, EraBag, Shield};
+    use std::sync::atomic::AtomicPtr;
+
+    // `all_eras` should return the eras published by the shields only.
+    #[test]
+    fn all_eras_published() {
+        let bag = EraBag::new();
+        let src = AtomicPtr::new(bag.alloc(0usize));
+        let shield = Shield::new(&bag);
+        assert!(bag.all_eras().is_empty());
+
+        let _ = shield.protect(&src);
+        assert_eq!(bag.all_eras(), [bag.era()]);
+
+        let _ = bag.advance();
+        let other = Shield::new(&bag);
+        let _ = other.protect(&src);
+        assert_eq!(bag.all_eras(), [bag.era() - 1, bag.era()]);
+
+        drop(shield);
+        drop(other);
+        assert!(bag.all_eras().is_empty());
+        // SAFETY: `src` is not shared.
+        unsafe { Block::<usize>::free(src.into_inner() as usize) };
+    }
+}
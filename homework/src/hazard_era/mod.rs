@@ -0,0 +1,71 @@
+//! Hazard eras.
+//!
+//! A variant of hazard pointers where a shield publishes an *era* of a global clock instead of an
+//! address. Each block records the era of its allocation (birth) and retirement, and a retired
+//! block is freed only if no shield publishes an era in between. As in hazard pointers, a shield
+//! protects only the pointer of its latest `protect`, since publishing a newer era may stop
+//! protecting the blocks retired meanwhile. But `protect` only has to publish a new era (with a
+//! fence) when the clock has advanced, so reusing a shield mostly costs a load of the clock.
+//!
+//! Blocks must be allocated with [`alloc`] (or [`EraBag::alloc`]) to record their birth era.
+//!
+//! Ramalhete and Correia.  Brief Announcement: Hazard Eras - Non-Blocking Memory Reclamation.
+//! SPAA 2017.
+//!
+//! # Example
+//!
+//! ```
+//! use std::ptr;
+//! use std::sync::atomic::{AtomicPtr, Ordering};
+//! use cs431_homework::hazard_era::{alloc, collect, retire, Shield};
+//!
+//! let shield = Shield::default();
+//! let atomic = AtomicPtr::new(alloc(1usize));
+//! let protected = shield.protect(&atomic);
+//! assert_eq!(unsafe { *protected }, 1);
+//!
+//! // unlink the block and retire
+//! atomic.store(ptr::null_mut(), Ordering::Relaxed);
+//! unsafe { retire(protected); }
+//!
+//! // manually trigger reclamation (not necessary)
+//! collect();
+//! ```
+
+use core::cell::RefCell;
+
+mod era;
+mod retire;
+
+pub use era::{EraBag, Shield};
+pub use retire::RetiredSet;
+
+/// Default global bag of all hazard eras.
+pub static ERAS: EraBag = EraBag::new();
+
+thread_local! {
+    /// Default thread-local retired pointer list.
+    static RETIRED: RefCell<RetiredSet<'static>> = RefCell::new(RetiredSet::default());
+}
+
+/// Allocates a block that can be protected by the default global bag.
+pub fn alloc<T>(data: T) -> *mut T {
+    ERAS.alloc(data)
+}
+
+/// Retires a pointer.
+///
+/// # Safety
+///
+/// * `pointer` must be allocated with [`alloc`].
+/// * `pointer` must be removed from shared memory before calling this function, and must be valid.
+/// * The same `pointer` should only be retired once.
+pub unsafe fn retire<T>(pointer: *mut T) {
+    RETIRED.with(|r| r.borrow_mut().retire(pointer));
+}
+
+/// Frees the pointers that are `retire`d by the current thread and not protected by the eras of
+/// any other threads.
+pub fn collect() {
+    RETIRED.with(|r| r.borrow_mut().collect());
+}
@@ -0,0 +1,213 @@
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::sync::atomic::{fence, Ordering};
+
+use super::{EraBag, ERAS};
+
+/// A block allocated with its birth era, which precedes the data.
+#[repr(C)]
+pub(crate) struct Block<T> {
+    birth: usize,
+    data: T,
+}
+
+impl<T> Block<T> {
+    /// The offset of `data` in the block.
+    fn offset() -> usize {
+        let block = MaybeUninit::<Self>::uninit();
+        let base = block.as_ptr();
+        // SAFETY: only the address of the field is computed.
+        unsafe { ptr::addr_of!((*base).data) as usize - base as usize }
+    }
+
+    /// Allocates a block born in `birth`, and returns the pointer to its data.
+    pub(crate) fn alloc(birth: usize, data: T) -> *mut T {
+        let block = Box::into_raw(Box::new(Self { birth, data }));
+        // SAFETY: `data` is in the allocation.
+        unsafe { ptr::addr_of_mut!((*block).data) }
+    }
+
+    /// Returns the block of `data`.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be allocated with `Block::alloc`.
+    unsafe fn of(data: *mut T) -> *mut Self {
+        data.cast::<u8>().sub(Self::offset()).cast()
+    }
+
+    /// Returns the birth era of `data`.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be allocated with `Block::alloc` and valid.
+    unsafe fn birth(data: *mut T) -> usize {
+        (*Self::of(data)).birth
+    }
+
+    /// Frees the block of `data`. This function is defined here instead of `collect()` as we know
+    /// about the type of `data` only at the time of retiring it.
+    ///
+    /// # Safety
+    ///
+    /// * `data` must be allocated with `Block::alloc`.
+    /// * Subsumes the safety requirements of [`Box::from_raw`]. In particular, one must have
+    ///   unique ownership to `data`.
+    ///
+    /// [`Box::from_raw`]: https://doc.rust-lang.org/std/boxed/struct.Box.html#method.from_raw
+    pub(crate) unsafe fn free(data: usize) {
+        drop(Box::from_raw(Self::of(data as *mut T)))
+    }
+}
+
+/// A retired pointer with the eras it was alive.
+#[derive(Debug)]
+struct Retired {
+    /// The machine representation of the pointer.
+    pointer: usize,
+    /// The function pointer to `Block::<T>::free` where `T` is the type of the object.
+    free: unsafe fn(usize),
+    birth: usize,
+    retire: usize,
+}
+
+/// Thread-local list of retired pointers.
+#[derive(Debug)]
+pub struct RetiredSet<'s> {
+    bag: &'s EraBag,
+    inner: Vec<Retired>,
+    _marker: PhantomData<*const ()>, // !Send + !Sync
+}
+
+impl<'s> RetiredSet<'s> {
+    /// The max length of retired pointer list. `collect` is triggered when `THRESHOLD` pointers
+    /// are retired.
+    const THRESHOLD: usize = 64;
+
+    /// Create a new retired pointer list protected by the given `EraBag`.
+    pub fn new(bag: &'s EraBag) -> Self {
+        Self {
+            bag,
+            inner: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Retires a pointer.
+    ///
+    /// # Safety
+    ///
+    /// * `pointer` must be allocated with [`EraBag::alloc`] of the bag of `self`.
+    /// * `pointer` must be removed from shared memory before calling this function, and must be
+    ///   valid.
+    /// * The same `pointer` should only be retired once.
+    ///
+    /// # Note
+    ///
+    /// `T: Send` is not required because a retired pointer is always freed by the thread that
+    /// retired it, either in `collect` or when its retired set is dropped.
+    ///
+    /// Triggers `collect` if the length of the list reaches `THRESHOLD`.
+    pub unsafe fn retire<T>(&mut self, pointer: *mut T) {
+        self.inner.push(Retired {
+            pointer: pointer as usize,
+            free: Block::<T>::free,
+            birth: Block::birth(pointer),
+            retire: self.bag.advance(),
+        });
+        if self.inner.len() >= Self::THRESHOLD {
+            self.collect();
+        }
+    }
+
+    /// Free the pointers that are `retire`d by the current thread and not protected by the eras of
+    /// any other threads, i.e. no era is published between their birth and retirement.
+    pub fn collect(&mut self) {
+        // SeqCst: pairs with the fence in `Shield::protect()`, so that either we see the eras, or
+        // the readers see that the retired pointers are unlinked.
+        fence(Ordering::SeqCst);
+        let eras = self.bag.all_eras();
+        self.inner.retain(|r| {
+            let i = eras.partition_point(|&era| era < r.birth);
+            let protected = eras.get(i).map_or(false, |&era| era <= r.retire);
+            if !protected {
+                // SAFETY: no other thread can access `r.pointer` anymore.
+                unsafe { (r.free)(r.pointer) };
+            }
+            protected
+        });
+    }
+}
+
+impl Default for RetiredSet<'static> {
+    fn default() -> Self {
+        Self::new(&ERAS)
+    }
+}
+
+impl Drop for RetiredSet<'_> {
+    fn drop(&mut self) {
+        // The remaining pointers are protected by the eras of the shields of the other threads,
+        // which publish a newer era or clear it once their operation ends. So unlike the retired
+        // sets of hazard pointers, this does not hand them over and waits instead, which
+        // terminates as long as no shield keeps an era between their birth and retirement.
+        while !self.inner.is_empty() {
+            self.collect();
+            std::thread::yield_now();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EraBag, RetiredSet};
+    use crate::hazard_era::Shield;
+    use std::cell::RefCell;
+    use std::collections::HashSet;
+    use std::rc::Rc;
+    use std::sync::atomic::AtomicPtr;
+
+    struct Tester(Rc<RefCell<HashSet<usize>>>, usize);
+    impl Drop for Tester {
+        fn drop(&mut self) {
+            let _ = self.0.borrow_mut().insert(self.1);
+        }
+    }
+
+    // retire `THRESHOLD` pointers to trigger collection
+    #[test]
+    fn retire_threshold_collect() {
+        let bag = EraBag::new();
+        let mut retires = RetiredSet::new(&bag);
+        let freed = Rc::new(RefCell::new(HashSet::new()));
+        for i in 0..RetiredSet::THRESHOLD {
+            unsafe { retires.retire(bag.alloc(Tester(freed.clone(), i))) };
+        }
+        let freed = Rc::try_unwrap(freed).unwrap().into_inner();
+
+        assert_eq!(freed, (0..RetiredSet::THRESHOLD).collect())
+    }
+
+    // only the pointers alive in a published era are not freed
+    #[test]
+    fn protected_interval() {
+        let bag = EraBag::new();
+        let mut retires = RetiredSet::new(&bag);
+        let freed = Rc::new(RefCell::new(HashSet::new()));
+
+        let src = AtomicPtr::new(bag.alloc(Tester(freed.clone(), 0)));
+        let shield = Shield::new(&bag);
+        let _ = shield.protect(&src);
+        unsafe { retires.retire(src.into_inner()) };
+        // born after the published era
+        let young = bag.alloc(Tester(freed.clone(), 1));
+        unsafe { retires.retire(young) };
+        retires.collect();
+        assert_eq!(*freed.borrow(), [1].into_iter().collect());
+
+        drop(shield);
+        retires.collect();
+        assert_eq!(*freed.borrow(), [0, 1].into_iter().collect());
+    }
+}
@@ -0,0 +1,115 @@
+use super::retire::RetiredPool;
+use super::{HazardBag, RetiredSet, Shield};
+
+/// A private domain of hazard pointers.
+///
+/// The hazard pointers of a domain protect only the pointers retired to the same domain, so
+/// unrelated data structures do not slow down each other's reclamation. The domain owns its
+/// hazard slots and retired pointer lists, and frees them when it is dropped.
+///
+/// The shields and retired sets of a domain borrow it, so they are dropped before the domain.
+#[derive(Debug)]
+pub struct HazardPointerDomain {
+    hazards: HazardBag,
+    retired: RetiredPool,
+}
+
+impl Default for HazardPointerDomain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HazardPointerDomain {
+    /// Creates a new domain.
+    pub fn new() -> Self {
+        Self {
+            hazards: HazardBag::new(),
+            retired: RetiredPool::default(),
+        }
+    }
+
+    /// Returns the bag of the hazard pointers of the domain.
+    pub fn hazards(&self) -> &HazardBag {
+        &self.hazards
+    }
+
+    /// Creates a shield in the domain.
+    pub fn shield(&self) -> Shield<'_> {
+        Shield::new(&self.hazards)
+    }
+
+    /// Creates `N` shields in the domain, acquiring their slots at once.
+    ///
+    /// For example, traversing a linked list needs to protect the previous, current and next nodes
+    /// at the same time.
+    pub fn shields<const N: usize>(&self) -> [Shield<'_>; N] {
+        Shield::many(&self.hazards)
+    }
+
+    /// Borrows a retired pointer list of the domain for the current thread.
+    ///
+    /// The domain reuses its lists: the returned set may hold the pointers retired by its previous
+    /// borrowers, and gives its pointers back to the domain when dropped, without waiting for them
+    /// to be unprotected. They are freed by a later borrower's `collect`, or when the domain is
+    /// dropped.
+    pub fn retired_set(&self) -> RetiredSet<'_> {
+        RetiredSet::from_pool(&self.hazards, &self.retired)
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use super::HazardPointerDomain;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // `shields` acquires distinct slots, and recycles the released ones
+    #[test]
+    fn shields_recycle() {
+        let domain = HazardPointerDomain::new();
+        let shields = domain.shields::<3>();
+        assert_eq!(domain.hazards().num_slots(), 3);
+        drop(shields);
+
+        let _shields = domain.shields::<2>();
+        let _shield = domain.shield();
+        assert_eq!(domain.hazards().num_slots(), 3);
+        let _more = domain.shields::<2>();
+        assert_eq!(domain.hazards().num_slots(), 5);
+    }
+
+    // a dropped retired set gives its pointers back to the domain, and the next borrower frees them
+    #[test]
+    fn retired_set_reuse() {
+        static FREED: AtomicUsize = AtomicUsize::new(0);
+        struct Tester;
+        impl Drop for Tester {
+            fn drop(&mut self) {
+                let _ = FREED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        let domain = HazardPointerDomain::new();
+        let shield = domain.shield();
+        let pointer = Box::into_raw(Box::new(Tester));
+        shield.set(pointer);
+        {
+            let mut retires = domain.retired_set();
+            unsafe { retires.retire(pointer) };
+            // does not wait although `pointer` is protected
+        }
+        assert_eq!(FREED.load(Ordering::Relaxed), 0);
+
+        let mut retires = domain.retired_set();
+        assert_eq!(retires.pending(), 1);
+        drop(shield);
+        retires.collect();
+        assert_eq!(FREED.load(Ordering::Relaxed), 1);
+        assert_eq!(retires.pending(), 0);
+
+        // the pointers left in the domain are freed with it
+        unsafe { retires.retire(Box::into_raw(Box::new(Tester))) };
+        drop(retires);
+        drop(domain);
+        assert_eq!(FREED.load(Ordering::Relaxed), 2);
+    }
+}
@@ -1,4 +1,6 @@
 use core::marker::PhantomData;
+use core::mem;
+use core::ops::Deref;
 use core::ptr::{self, NonNull};
 use std::collections::HashSet;
 use std::fmt;
@@ -8,17 +10,21 @@ use core::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicUsize, Ordering};
 #[cfg(feature = "check-loom")]
 use loom::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicUsize, Ordering};
 
-use super::HAZARDS;
+use crossbeam_epoch::{self as epoch, Shared};
 
-/// Represents the ownership of a hazard pointer slot.
-pub struct Shield {
+use super::membarrier;
+use super::retire::Orphans;
+use super::{retire, HAZARDS};
+
+/// Represents the ownership of a hazard pointer slot of the bag `'h`.
+pub struct Shield<'h> {
     slot: NonNull<HazardSlot>,
-    _marker: PhantomData<*mut ()>, // !Send + !Sync
+    _marker: PhantomData<(&'h HazardBag, *mut ())>, // !Send + !Sync
 }
 
-impl Shield {
+impl<'h> Shield<'h> {
     /// Creates a new shield for hazard pointer.
-    pub fn new(hazards: &HazardBag) -> Self {
+    pub fn new(hazards: &'h HazardBag) -> Self {
         let slot = hazards.acquire_slot();
         Self {
             slot: slot.into(),
@@ -26,6 +32,16 @@ impl Shield {
         }
     }
 
+    /// Creates `N` shields, acquiring their slots at once.
+    ///
+    /// See [`HazardBag::acquire_slots`].
+    pub fn many<const N: usize>(hazards: &'h HazardBag) -> [Self; N] {
+        hazards.acquire_slots::<N>().map(|slot| Self {
+            slot: slot.into(),
+            _marker: PhantomData,
+        })
+    }
+
     /// Store `pointer` to the hazard slot.
     pub fn set<T>(&self, pointer: *mut T) {
         todo!()
@@ -41,6 +57,9 @@ impl Shield {
     /// For a pointer `p`, if "`src` still pointing to `pointer`" implies that `p` is not retired,
     /// then `Ok(())` means that shields set to `p` are validated.
     pub fn validate<T>(pointer: *mut T, src: &AtomicPtr<T>) -> Result<(), *mut T> {
+        // Pairs with the fence in `collect()`, so that either it sees the hazard, or we see that
+        // `src` no longer points to `pointer`.
+        membarrier::light();
         todo!()
     }
 
@@ -68,22 +87,44 @@ impl Shield {
         }
         pointer
     }
+
+    /// Get a protected pointer from `src` as a typed reference, which clears this shield when
+    /// dropped. Returns `None` if `src` is null, in which case this shield is cleared.
+    ///
+    /// # Safety
+    ///
+    /// Every non-null pointer stored in `src` must be valid, and must be retired only after it is
+    /// unlinked from `src`. Then "`src` still pointing to `pointer`" implies that `pointer` is not
+    /// retired, so the protected pointer is valid while the shield is not reused.
+    pub unsafe fn protect_typed<'s, T>(
+        &'s mut self,
+        src: &AtomicPtr<T>,
+    ) -> Option<Protected<'s, 'h, T>> {
+        let pointer = NonNull::new(self.protect(src));
+        if pointer.is_none() {
+            self.clear();
+        }
+        pointer.map(|pointer| Protected {
+            shield: self,
+            pointer,
+        })
+    }
 }
 
-impl Default for Shield {
+impl Default for Shield<'static> {
     fn default() -> Self {
         Self::new(&HAZARDS)
     }
 }
 
-impl Drop for Shield {
+impl Drop for Shield<'_> {
     /// Clear and release the ownership of the hazard slot.
     fn drop(&mut self) {
         todo!()
     }
 }
 
-impl fmt::Debug for Shield {
+impl fmt::Debug for Shield<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Shield")
             .field("slot address", &self.slot)
@@ -92,12 +133,107 @@ impl fmt::Debug for Shield {
     }
 }
 
+/// A pointer protected by a [`Shield`], obtained from [`Shield::protect_typed`].
+///
+/// It mutably borrows the shield so that the shield cannot be reused while the pointer is
+/// protected, and clears the shield when dropped.
+pub struct Protected<'s, 'h, T> {
+    shield: &'s mut Shield<'h>,
+    pointer: NonNull<T>,
+}
+
+impl<'s, 'h, T> Protected<'s, 'h, T> {
+    /// Returns the raw pointer.
+    pub fn as_ptr(&self) -> *mut T {
+        self.pointer.as_ptr()
+    }
+
+    /// Releases the shield without clearing it.
+    fn into_shield(self) -> &'s mut Shield<'h> {
+        let this = mem::ManuallyDrop::new(self);
+        // SAFETY: `this` is not dropped, so `shield` is moved out only once.
+        unsafe { ptr::read(&this.shield) }
+    }
+
+    /// Protects the pointer from `src` with the same shield instead. The shield is overwritten
+    /// rather than cleared in between.
+    ///
+    /// # Safety
+    ///
+    /// See [`Shield::protect_typed`].
+    pub unsafe fn retarget(self, src: &AtomicPtr<T>) -> Option<Self> {
+        self.into_shield().protect_typed(src)
+    }
+
+    /// Clears the shield and retires the pointer.
+    ///
+    /// # Safety
+    ///
+    /// * The pointer must be unlinked from shared memory by the current thread.
+    /// * The same pointer should only be retired once.
+    pub unsafe fn retire(self)
+    where
+        T: Send,
+    {
+        let pointer = self.as_ptr();
+        drop(self);
+        retire(pointer);
+    }
+}
+
+impl<T> Deref for Protected<'_, '_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the pointer is valid while it is protected. See `Shield::protect_typed`.
+        unsafe { self.pointer.as_ref() }
+    }
+}
+
+impl<T> Drop for Protected<'_, '_, T> {
+    fn drop(&mut self) {
+        self.shield.clear();
+    }
+}
+
+impl<T> fmt::Debug for Protected<'_, '_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Protected")
+            .field("pointer", &self.pointer)
+            .field("shield", &self.shield)
+            .finish()
+    }
+}
+
 /// Global bag (multiset) of hazards pointers.
-/// `HazardBag.head` and `HazardSlot.next` form a grow-only list of all hazard slots. Slots are
-/// never removed from this list. Instead, it gets deactivated and recycled for other `Shield`s.
+/// `HazardBag.head` and `HazardSlot.next` form a list of all hazard slots. When a `Shield` is
+/// dropped, its slot gets deactivated and recycled for other `Shield`s. New slots are only pushed
+/// to the head, and inactive slots are removed only by [`HazardBag::compact`], so that the cost of
+/// scanning the list tracks the number of live shields after a burst of short-lived threads.
+///
+/// As removed slots are freed with `crossbeam_epoch`, a traversal of the list should be done while
+/// pinned.
 #[derive(Debug)]
 pub struct HazardBag {
     head: AtomicPtr<HazardSlot>,
+    /// Whether a thread is compacting the list. Compaction is serialized, so that the `next` of a
+    /// slot in the list is modified only by the compacting thread.
+    compacting: AtomicBool,
+    /// The number of `compact_if_sparse` calls.
+    compact_calls: AtomicUsize,
+    /// Retired pointers left by exited threads.
+    pub(crate) orphans: Orphans,
+}
+
+/// Statistics of the slots of a [`HazardBag`], returned by [`HazardBag::stats`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HazardStats {
+    /// The number of slots, including the inactive ones.
+    pub slots: usize,
+    /// The number of slots occupied by a `Shield`.
+    pub active: usize,
+    /// The number of slots protecting a non-null pointer.
+    pub hazards: usize,
 }
 
 /// See `HazardBag`
@@ -107,8 +243,8 @@ struct HazardSlot {
     active: AtomicBool,
     // Machine representation of the hazard pointer.
     hazard: AtomicUsize,
-    // Immutable pointer to the next slot in the bag.
-    next: *const HazardSlot,
+    // Pointer to the next slot in the bag, modified only by `HazardBag::compact`.
+    next: AtomicPtr<HazardSlot>,
 }
 
 impl HazardSlot {
@@ -118,11 +254,18 @@ impl HazardSlot {
 }
 
 impl HazardBag {
+    /// `compact_if_sparse` checks the sparsity of the list once in `COMPACT_PERIOD` calls, so that
+    /// its O(H) traversal is amortized over the collections.
+    const COMPACT_PERIOD: usize = 16;
+
     #[cfg(not(feature = "check-loom"))]
     /// Creates a new global hazard set.
     pub const fn new() -> Self {
         Self {
             head: AtomicPtr::new(ptr::null_mut()),
+            compacting: AtomicBool::new(false),
+            compact_calls: AtomicUsize::new(0),
+            orphans: Orphans::new(),
         }
     }
 
@@ -131,6 +274,9 @@ impl HazardBag {
     pub fn new() -> Self {
         Self {
             head: AtomicPtr::new(ptr::null_mut()),
+            compacting: AtomicBool::new(false),
+            compact_calls: AtomicUsize::new(0),
+            orphans: Orphans::new(),
         }
     }
 
@@ -140,12 +286,170 @@ impl HazardBag {
         todo!()
     }
 
+    /// Acquires `N` slots at once: the inactive slots found in a single traversal are recycled,
+    /// and the rest are allocated as a chain and pushed with a single CAS.
+    fn acquire_slots<const N: usize>(&self) -> [&HazardSlot; N] {
+        let mut slots = [None; N];
+        let mut count = 0;
+
+        let _guard = epoch::pin();
+        let mut slot = self.head.load(Ordering::Acquire);
+        while count < N {
+            // SAFETY: we are pinned, so the slot is not freed even if it is removed.
+            let Some(s) = (unsafe { slot.as_ref() }) else {
+                break;
+            };
+            if s.active
+                .compare_exchange(false, true, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+            {
+                slots[count] = Some(s);
+                count += 1;
+            }
+            slot = s.next.load(Ordering::Acquire);
+        }
+
+        if count < N {
+            let mut first = ptr::null_mut::<HazardSlot>();
+            let mut last = ptr::null_mut::<HazardSlot>();
+            while count < N {
+                let new = Box::into_raw(Box::new(HazardSlot {
+                    active: AtomicBool::new(true),
+                    hazard: AtomicUsize::new(0),
+                    next: AtomicPtr::new(first),
+                }));
+                if last.is_null() {
+                    last = new;
+                }
+                first = new;
+                // SAFETY: an active slot is never removed.
+                slots[count] = Some(unsafe { &*new });
+                count += 1;
+            }
+
+            let mut head = self.head.load(Ordering::Relaxed);
+            loop {
+                // SAFETY: the chain is not shared until the CAS succeeds.
+                unsafe { (*last).next.store(head, Ordering::Relaxed) };
+                // Release: the other threads traversing the bag should see the new slots.
+                match self
+                    .head
+                    .compare_exchange(head, first, Ordering::Release, Ordering::Relaxed)
+                {
+                    Ok(_) => break,
+                    Err(current) => head = current,
+                }
+            }
+        }
+
+        slots.map(Option::unwrap)
+    }
+
     /// Find an inactive slot and activate it.
+    ///
+    /// The traversal should be done while pinned with `crossbeam_epoch`, as the slots removed by
+    /// [`HazardBag::compact`] are freed with it. An active slot is never removed, so the returned
+    /// slot stays valid after unpinning.
     fn try_acquire_inactive(&self) -> Option<&HazardSlot> {
         todo!()
     }
 
+    /// Returns the statistics of the slots, for debugging.
+    pub fn stats(&self) -> HazardStats {
+        let mut stats = HazardStats::default();
+        let _guard = epoch::pin();
+        let mut slot = self.head.load(Ordering::Acquire);
+        // SAFETY: we are pinned, so the slots are not freed even if they are removed.
+        while let Some(s) = unsafe { slot.as_ref() } {
+            stats.slots += 1;
+            if s.active.load(Ordering::Relaxed) {
+                stats.active += 1;
+            }
+            if s.hazard.load(Ordering::Relaxed) != 0 {
+                stats.hazards += 1;
+            }
+            slot = s.next.load(Ordering::Acquire);
+        }
+        stats
+    }
+
+    /// Returns the number of hazard slots, including the inactive ones.
+    pub fn num_slots(&self) -> usize {
+        let mut count = 0;
+        let _guard = epoch::pin();
+        let mut slot = self.head.load(Ordering::Acquire);
+        // SAFETY: we are pinned, so the slots are not freed even if they are removed.
+        while let Some(s) = unsafe { slot.as_ref() } {
+            count += 1;
+            slot = s.next.load(Ordering::Acquire);
+        }
+        count
+    }
+
+    /// Removes the inactive slots from the list. Returns immediately if another thread is
+    /// compacting the list.
+    pub fn compact(&self) {
+        if self
+            .compacting
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return;
+        }
+
+        let guard = epoch::pin();
+        let mut prev = &self.head;
+        let mut curr = prev.load(Ordering::Acquire);
+        // SAFETY: we are pinned, and only this thread removes slots.
+        while let Some(c) = unsafe { curr.as_ref() } {
+            let next = c.next.load(Ordering::Acquire);
+            // Claim the slot, so that no other thread acquires it while it is being removed.
+            if c.active
+                .compare_exchange(false, true, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+            {
+                // Release: the traversals from `prev` should see the rest of the list.
+                let removed = if ptr::eq(prev, &self.head) {
+                    // New slots may have been pushed in the meantime.
+                    self.head
+                        .compare_exchange(curr, next, Ordering::Release, Ordering::Relaxed)
+                        .is_ok()
+                } else {
+                    prev.store(next, Ordering::Release);
+                    true
+                };
+                if removed {
+                    // SAFETY: the slot is removed, and the traversals that may still read it are
+                    // pinned.
+                    unsafe { guard.defer_destroy(Shared::from(curr.cast_const())) };
+                    curr = next;
+                    continue;
+                }
+                c.active.store(false, Ordering::Release);
+            }
+            prev = &c.next;
+            curr = next;
+        }
+
+        self.compacting.store(false, Ordering::Release);
+    }
+
+    /// Compacts the list if most of the slots are inactive. Called by each `collect`, but checks
+    /// the list only once in `COMPACT_PERIOD` calls.
+    pub(crate) fn compact_if_sparse(&self) {
+        if self.compact_calls.fetch_add(1, Ordering::Relaxed) % Self::COMPACT_PERIOD != 0 {
+            return;
+        }
+        let stats = self.stats();
+        if stats.active * 2 < stats.slots {
+            self.compact();
+        }
+    }
+
     /// Returns all the hazards in the set.
+    ///
+    /// The traversal should be done while pinned with `crossbeam_epoch`, as the slots removed by
+    /// [`HazardBag::compact`] are freed with it.
     pub fn all_hazards(&self) -> HashSet<usize> {
         todo!()
     }
@@ -222,6 +526,74 @@ mod tests {
         assert!(intersection.is_empty())
     }
 
+    // a `Protected` should clear its shield when dropped, but not when retargeted.
+    #[test]
+    fn protected_clear() {
+        let hazard_bag = HazardBag::new();
+        let mut shield = Shield::new(&hazard_bag);
+        let first = AtomicPtr::new(1 as *mut usize);
+        let second = AtomicPtr::new(2 as *mut usize);
+
+        let protected = unsafe { shield.protect_typed(&first) }.unwrap();
+        assert_eq!(protected.as_ptr() as usize, 1);
+        let protected = unsafe { protected.retarget(&second) }.unwrap();
+        assert_eq!(hazard_bag.all_hazards(), [2].into_iter().collect());
+        drop(protected);
+        assert!(hazard_bag.all_hazards().is_empty());
+
+        assert!(unsafe { shield.protect_typed(&AtomicPtr::<usize>::default()) }.is_none());
+        assert!(hazard_bag.all_hazards().is_empty());
+    }
+
+    // `stats` should count the slots, the shields and the protected pointers.
+    #[test]
+    fn stats() {
+        let hazard_bag = HazardBag::new();
+        let src = AtomicPtr::new(1 as *mut ());
+        let mut shields = (0..4).map(|_| Shield::new(&hazard_bag)).collect::<Vec<_>>();
+        let _ = shields[0].protect(&src);
+        drop(shields.pop());
+        let stats = hazard_bag.stats();
+        assert_eq!((stats.slots, stats.active, stats.hazards), (4, 3, 1));
+        drop(shields);
+    }
+
+    // `compact` should remove the inactive slots only.
+    #[test]
+    fn compact() {
+        let hazard_bag = HazardBag::new();
+        let mut shields = (0..8).map(|_| Shield::new(&hazard_bag)).collect::<Vec<_>>();
+        let src = AtomicPtr::new(1 as *mut ());
+        let _ = shields[1].protect(&src);
+        shields.truncate(2);
+        hazard_bag.compact();
+        assert_eq!(hazard_bag.num_slots(), 2);
+        assert_eq!(hazard_bag.all_hazards(), [1].into_iter().collect());
+
+        // new slots can be acquired after compaction
+        shields.push(Shield::new(&hazard_bag));
+        assert_eq!(hazard_bag.num_slots(), 3);
+    }
+
+    // `compact_if_sparse` should check the list only once in `COMPACT_PERIOD` calls.
+    #[test]
+    fn compact_if_sparse_period() {
+        let hazard_bag = HazardBag::new();
+        let mut shields = (0..8).map(|_| Shield::new(&hazard_bag)).collect::<Vec<_>>();
+        shields.truncate(2);
+        hazard_bag.compact_if_sparse();
+        assert_eq!(hazard_bag.num_slots(), 2);
+
+        shields.extend((0..6).map(|_| Shield::new(&hazard_bag)));
+        shields.truncate(2);
+        for _ in 1..HazardBag::COMPACT_PERIOD {
+            hazard_bag.compact_if_sparse();
+            assert_eq!(hazard_bag.num_slots(), 8);
+        }
+        hazard_bag.compact_if_sparse();
+        assert_eq!(hazard_bag.num_slots(), 2);
+    }
+
     // `acquire_slot` should recycle existing slots.
     #[test]
     fn recycle_slots() {
@@ -0,0 +1,201 @@
+use core::mem;
+use core::ptr;
+
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicPtr, Ordering::*};
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicPtr, Ordering::*};
+
+use super::{retire, Shield, HAZARDS};
+use crate::ConcurrentSet;
+
+/// Michael's ordered list-based set protected by hazard pointers.
+///
+/// A node is logically removed by marking the lowest bit of its `next` pointer, and then physically
+/// removed (and retired) by the traversals.
+///
+/// Michael.  High Performance Dynamic Lock-Free Hash Tables and List-Based Sets.  SPAA 2002.
+#[derive(Debug)]
+pub struct ListSet<T> {
+    head: AtomicPtr<Node<T>>,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    key: T,
+    next: AtomicPtr<Node<T>>,
+}
+
+unsafe impl<T: Send + Sync> Sync for ListSet<T> {}
+unsafe impl<T: Send + Sync> Send for ListSet<T> {}
+
+/// The mark bit of a `next` pointer.
+const MARK: usize = 1;
+
+fn is_marked<T>(pointer: *mut T) -> bool {
+    pointer as usize & MARK != 0
+}
+
+fn marked<T>(pointer: *mut T) -> *mut T {
+    (pointer as usize | MARK) as *mut T
+}
+
+fn unmarked<T>(pointer: *mut T) -> *mut T {
+    (pointer as usize & !MARK) as *mut T
+}
+
+/// Position in the list found by `ListSet::find`.
+struct Cursor<'s, T> {
+    /// The link to `curr`, which is the set's `head` or the `next` of a node protected by
+    /// `shields[0]`.
+    prev: &'s AtomicPtr<Node<T>>,
+    /// The first node whose key is not less than the key searched for, protected by `shields[1]`.
+    curr: *mut Node<T>,
+}
+
+impl<T> Default for ListSet<T> {
+    fn default() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+}
+
+impl<T: Ord + Send> ListSet<T> {
+    /// Creates a new set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Finds the position of `key`, and returns whether it is in the set. Marked nodes on the way
+    /// are unlinked and retired.
+    ///
+    /// `find` rotates the roles of `shields`, and protects the nodes of the returned cursor anew.
+    /// Hence the callers may reuse `shields` across calls, as `insert` and `remove` do on retries.
+    fn find<'s>(&'s self, key: &T, shields: &'s mut [Shield<'static>; 3]) -> (bool, Cursor<'s, T>) {
+        'retry: loop {
+            let mut prev = &self.head;
+            // The head is never marked.
+            let mut curr = shields[1].protect(prev);
+            loop {
+                if curr.is_null() {
+                    return (false, Cursor { prev, curr });
+                }
+                // SAFETY: `curr` is protected and validated by `prev`, which is not marked.
+                let curr_ref = unsafe { &*curr };
+
+                let next = curr_ref.next.load(Acquire);
+                shields[2].set(unmarked(next));
+                // Validation: if `curr.next` still points to `next`, then `next` is not retired
+                // unless `curr` is. And `curr` is not retired as `prev` still points to it.
+                if Shield::validate(next, &curr_ref.next).is_err() || prev.load(Acquire) != curr {
+                    continue 'retry;
+                }
+
+                if !is_marked(next) {
+                    if curr_ref.key >= *key {
+                        return (curr_ref.key == *key, Cursor { prev, curr });
+                    }
+                    prev = &curr_ref.next;
+                    // `shields[0]` protects the node of `prev`.
+                    shields.rotate_left(1);
+                } else {
+                    if prev
+                        .compare_exchange(curr, unmarked(next), Release, Relaxed)
+                        .is_err()
+                    {
+                        continue 'retry;
+                    }
+                    // SAFETY: `curr` is unlinked by the above CAS.
+                    unsafe { retire(curr) };
+                    shields.swap(1, 2);
+                }
+                curr = unmarked(next);
+            }
+        }
+    }
+}
+
+impl<T: Ord + Send> ConcurrentSet<T> for ListSet<T> {
+    fn contains(&self, key: &T) -> bool {
+        let mut shields = Shield::many::<3>(&HAZARDS);
+        self.find(key, &mut shields).0
+    }
+
+    fn insert(&self, key: T) -> bool {
+        let mut shields = Shield::many::<3>(&HAZARDS);
+        let node = Box::into_raw(Box::new(Node {
+            key,
+            next: AtomicPtr::new(ptr::null_mut()),
+        }));
+        loop {
+            // SAFETY: `node` is not shared until the CAS succeeds.
+            let (found, cursor) = self.find(unsafe { &(*node).key }, &mut shields);
+            if found {
+                // SAFETY: `node` was never shared.
+                drop(unsafe { Box::from_raw(node) });
+                return false;
+            }
+            unsafe { (*node).next.store(cursor.curr, Relaxed) };
+            if cursor
+                .prev
+                .compare_exchange(cursor.curr, node, Release, Relaxed)
+                .is_ok()
+            {
+                return true;
+            }
+        }
+    }
+
+    fn remove(&self, key: &T) -> bool {
+        let mut shields = Shield::many::<3>(&HAZARDS);
+        loop {
+            let (found, cursor) = self.find(key, &mut shields);
+            if !found {
+                return false;
+            }
+            // SAFETY: `cursor.curr` is protected.
+            let curr_ref = unsafe { &*cursor.curr };
+            let next = curr_ref.next.load(Acquire);
+            if is_marked(next)
+                || curr_ref
+                    .next
+                    .compare_exchange(next, marked(next), Acquire, Relaxed)
+                    .is_err()
+            {
+                continue;
+            }
+            if cursor
+                .prev
+                .compare_exchange(cursor.curr, next, Release, Relaxed)
+                .is_ok()
+            {
+                // SAFETY: `cursor.curr` is unlinked by the above CAS.
+                unsafe { retire(cursor.curr) };
+            } else {
+                // Let a traversal unlink it.
+                let _ = self.find(key, &mut shields);
+            }
+            return true;
+        }
+    }
+}
+
+impl<T> Drop for ListSet<T> {
+    #[cfg(feature = "check-loom")]
+    fn drop(&mut self) {
+        let mut curr = self.head.load(Relaxed);
+        while !curr.is_null() {
+            let curr_ref = unsafe { Box::from_raw(unmarked(curr)) };
+            curr = curr_ref.next.load(Relaxed);
+        }
+    }
+    #[cfg(not(feature = "check-loom"))]
+    fn drop(&mut self) {
+        let mut curr = *self.head.get_mut();
+        while !curr.is_null() {
+            let curr_ref = unsafe { Box::from_raw(unmarked(curr)) };
+            curr = curr_ref.next.into_inner();
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! Asymmetric fences.
+//!
+//! Protecting a pointer requires a `SeqCst` fence between setting the hazard slot and re-reading
+//! the source, which pairs with the fence in `collect()` between unlinking the pointers and reading
+//! the hazard slots. As protection is much more frequent than collection, with the
+//! `asymmetric-fence` feature the reader side [`light`] is only a compiler fence, and the reclaimer
+//! side [`heavy`] issues a process-wide barrier with the Linux `membarrier` system call, which
+//! executes a memory barrier on every running thread of the process.
+//!
+//! If the feature is disabled or `membarrier` is not supported, both sides are `SeqCst` fences.
+
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{fence, Ordering};
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{fence, Ordering};
+
+cfg_if::cfg_if! {
+    if #[cfg(all(
+        feature = "asymmetric-fence",
+        target_os = "linux",
+        not(feature = "check-loom")
+    ))] {
+        use core::sync::atomic::{compiler_fence, AtomicU8};
+
+        /// Asks for the supported commands.
+        const MEMBARRIER_CMD_QUERY: libc::c_int = 0;
+        /// Executes a memory barrier on the running threads of the calling process.
+        const MEMBARRIER_CMD_PRIVATE_EXPEDITED: libc::c_int = 1 << 3;
+        /// Registers the intention to use `MEMBARRIER_CMD_PRIVATE_EXPEDITED`.
+        const MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED: libc::c_int = 1 << 4;
+
+        const UNKNOWN: u8 = 0;
+        const SUPPORTED: u8 = 1;
+        const UNSUPPORTED: u8 = 2;
+
+        /// Whether `membarrier` can be used. Decided once and never changes afterwards, so that
+        /// both sides agree on the kind of the fences.
+        static STATE: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+        fn membarrier(cmd: libc::c_int) -> libc::c_long {
+            // SAFETY: `membarrier` does not access memory of the process.
+            unsafe { libc::syscall(libc::SYS_membarrier, cmd, 0, 0) }
+        }
+
+        #[cold]
+        fn register() -> u8 {
+            let commands = membarrier(MEMBARRIER_CMD_QUERY);
+            let state = if commands >= 0
+                && commands & libc::c_long::from(MEMBARRIER_CMD_PRIVATE_EXPEDITED) != 0
+                && membarrier(MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED) == 0
+            {
+                SUPPORTED
+            } else {
+                UNSUPPORTED
+            };
+            // All the threads compute the same result, so it does not matter who wins.
+            match STATE.compare_exchange(UNKNOWN, state, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => state,
+                Err(current) => current,
+            }
+        }
+
+        fn supported() -> bool {
+            let state = match STATE.load(Ordering::Relaxed) {
+                UNKNOWN => register(),
+                state => state,
+            };
+            state == SUPPORTED
+        }
+
+        /// The reader side of the asymmetric fence.
+        #[inline]
+        pub(crate) fn light() {
+            if supported() {
+                compiler_fence(Ordering::SeqCst);
+            } else {
+                fence(Ordering::SeqCst);
+            }
+        }
+
+        /// The reclaimer side of the asymmetric fence.
+        pub(crate) fn heavy() {
+            fence(Ordering::SeqCst);
+            if supported() && membarrier(MEMBARRIER_CMD_PRIVATE_EXPEDITED) != 0 {
+                // The registration succeeded, so this is unreachable in practice.
+                panic!("membarrier failed");
+            }
+        }
+    } else {
+        /// The reader side of the asymmetric fence.
+        #[inline]
+        pub(crate) fn light() {
+            fence(Ordering::SeqCst);
+        }
+
+        /// The reclaimer side of the asymmetric fence.
+        pub(crate) fn heavy() {
+            fence(Ordering::SeqCst);
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use super::{heavy, light};
+    use std::thread::scope;
+
+    // the heavy fence does not fail while the other threads issue light fences
+    #[test]
+    fn concurrent_fences() {
+        scope(|s| {
+            for _ in 0..4 {
+                let _unused = s.spawn(|| {
+                    for _ in 0..1024 {
+                        light();
+                    }
+                });
+            }
+            for _ in 0..16 {
+                heavy();
+            }
+        });
+    }
+}
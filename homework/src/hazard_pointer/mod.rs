@@ -27,11 +27,22 @@ use loom::thread_local;
 #[cfg(not(feature = "check-loom"))]
 use std::thread_local;
 
+mod domain;
 mod hazard;
+mod list_set;
+mod membarrier;
+mod queue;
+#[cfg(not(feature = "check-loom"))]
+mod reclaim;
 mod retire;
 
-pub use hazard::{HazardBag, Shield};
-pub use retire::RetiredSet;
+pub use domain::HazardPointerDomain;
+pub use hazard::{HazardBag, HazardStats, Protected, Shield};
+pub use list_set::ListSet;
+pub use queue::Queue;
+#[cfg(not(feature = "check-loom"))]
+pub use reclaim::{HazardGuard, HazardPointers};
+pub use retire::{RetiredSet, RetiredSetBuilder};
 
 #[cfg(not(feature = "check-loom"))]
 /// Default global bag of all hazard pointers.
@@ -56,10 +67,26 @@ thread_local! {
 ///
 /// * `pointer` must be removed from shared memory before calling this function, and must be valid.
 /// * The same `pointer` should only be retired once.
-pub unsafe fn retire<T>(pointer: *mut T) {
+pub unsafe fn retire<T: Send>(pointer: *mut T) {
     RETIRED.with(|r| r.borrow_mut().retire(pointer));
 }
 
+/// Retires a pointer that is freed by `free`. See [`RetiredSet::retire_with`].
+///
+/// # Safety
+///
+/// * `pointer` must be removed from shared memory before calling this function, and must be valid.
+/// * The same `pointer` should only be retired once.
+/// * It must be safe to call `free(pointer)` from any thread once `pointer` is not protected.
+pub unsafe fn retire_with<T: Send>(pointer: *mut T, free: unsafe fn(*mut ())) {
+    RETIRED.with(|r| r.borrow_mut().retire_with(pointer, free));
+}
+
+/// Defers the execution of `f` to a later `collect`. See [`RetiredSet::defer`].
+pub fn defer<F: FnOnce() + Send + 'static>(f: F) {
+    RETIRED.with(|r| r.borrow_mut().defer(f));
+}
+
 /// Frees the pointers that are `retire`d by the current thread and not `protect`ed by any other
 /// threads.
 pub fn collect() {
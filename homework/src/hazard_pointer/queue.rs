@@ -0,0 +1,157 @@
+use core::mem::MaybeUninit;
+use core::ptr;
+
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicPtr, Ordering::*};
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicPtr, Ordering::*};
+
+use super::{retire, Shield};
+
+/// Michael-Scott queue protected by hazard pointers.
+#[derive(Debug)]
+pub struct Queue<T> {
+    head: AtomicPtr<Node<T>>,
+    tail: AtomicPtr<Node<T>>,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    data: MaybeUninit<T>,
+    next: AtomicPtr<Node<T>>,
+}
+
+unsafe impl<T: Send> Sync for Queue<T> {}
+unsafe impl<T: Send> Send for Queue<T> {}
+
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        let q = Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+            tail: AtomicPtr::new(ptr::null_mut()),
+        };
+        let sentinel = Box::leak(Box::new(Node {
+            data: MaybeUninit::uninit(),
+            next: AtomicPtr::new(ptr::null_mut()),
+        }));
+        q.head.store(sentinel, Relaxed);
+        q.tail.store(sentinel, Relaxed);
+        q
+    }
+}
+
+impl<T: Send> Queue<T> {
+    /// Adds `t` to the back of the queue.
+    pub fn push(&self, t: T) {
+        let new = Box::leak(Box::new(Node {
+            data: MaybeUninit::new(t),
+            next: AtomicPtr::new(ptr::null_mut()),
+        }));
+        let mut shield = Shield::default();
+
+        loop {
+            // SAFETY
+            // 1. queue's `tail` is always valid as it will be CASed with valid nodes only.
+            // 2. A node is retired only after `head` and `tail` moved past it.
+            let tail = unsafe { shield.protect_typed(&self.tail) }.unwrap();
+
+            let next = tail.next.load(Acquire);
+            if !next.is_null() {
+                let _ = self
+                    .tail
+                    .compare_exchange(tail.as_ptr(), next, Release, Relaxed);
+                continue;
+            }
+
+            if tail
+                .next
+                .compare_exchange(ptr::null_mut(), new, Release, Relaxed)
+                .is_ok()
+            {
+                let _ = self
+                    .tail
+                    .compare_exchange(tail.as_ptr(), new, Release, Relaxed);
+                break;
+            }
+        }
+    }
+
+    /// Removes an element from the front of the queue and returns it, or `None` if the queue is
+    /// empty.
+    pub fn try_pop(&self) -> Option<T> {
+        let head_shield = Shield::default();
+        let next_shield = Shield::default();
+        let mut head = self.head.load(Acquire);
+        loop {
+            if let Err(new) = head_shield.try_protect(head, &self.head) {
+                head = new;
+                continue;
+            }
+            // SAFETY:
+            // 1. queue's `head` is always valid as it will be CASed with valid nodes only.
+            // 2. `head` is protected & validated.
+            let head_ref = unsafe { &*head };
+
+            let next = head_ref.next.load(Acquire);
+            if next.is_null() {
+                return None;
+            }
+            next_shield.set(next);
+            let next_ref = match Shield::validate(head, &self.head) {
+                Ok(_) => {
+                    // SAFETY:
+                    // 1. If `next` was not null, then it must be a valid node that another
+                    //    thread has `push()`ed.
+                    // 2. Validation: If `head` is not retired, then `next` is not retired. So
+                    //    re-validating `head` also validates `next.
+                    unsafe { &*next }
+                }
+                Err(new) => {
+                    next_shield.clear();
+                    head = new;
+                    continue;
+                }
+            };
+
+            let tail = self.tail.load(Relaxed);
+            if tail == head {
+                let _ = self.tail.compare_exchange(tail, next, Release, Relaxed);
+            }
+
+            if self
+                .head
+                .compare_exchange(head, next, Release, Relaxed)
+                .is_ok()
+            {
+                let result = unsafe { next_ref.data.assume_init_read() };
+                unsafe { retire(head) };
+                return Some(result);
+            }
+        }
+    }
+}
+
+impl<T> Drop for Queue<T> {
+    #[cfg(feature = "check-loom")]
+    fn drop(&mut self) {
+        let sentinel = unsafe { Box::from_raw(self.head.load(Relaxed)) };
+
+        let mut curr = sentinel.next.load(Relaxed);
+        while !curr.is_null() {
+            let curr_ref = unsafe { Box::from_raw(curr) };
+            drop(unsafe { curr_ref.data.assume_init() });
+            curr = curr_ref.next.load(Relaxed);
+        }
+    }
+    #[cfg(not(feature = "check-loom"))]
+    fn drop(&mut self) {
+        let sentinel = unsafe { Box::from_raw(*self.head.get_mut()) };
+
+        let mut curr = sentinel.next.into_inner();
+        while !curr.is_null() {
+            let curr_ref = unsafe { Box::from_raw(curr) };
+            drop(unsafe { curr_ref.data.assume_init() });
+            curr = curr_ref.next.into_inner();
+        }
+    }
+}
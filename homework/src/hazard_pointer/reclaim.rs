@@ -0,0 +1,113 @@
+//! Hazard pointers as a [`Reclaimer`] for the data structures in [`cs431::lockfree`].
+
+use core::cell::RefCell;
+use core::sync::atomic::Ordering;
+
+use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Shared};
+use cs431::reclaim::Reclaimer;
+
+use super::{membarrier, retire_with, Shield};
+
+/// Hazard pointer based reclamation with the global [`HAZARDS`](super::HAZARDS) and the
+/// thread-local retired set.
+///
+/// Each pointer protected with a [`HazardGuard`] takes a shield of the guard. The shield is reused
+/// once the pointer is released with [`Reclaimer::release`], which the data structures do for the
+/// nodes they pass by or retry from. Hence, the number of shields of a guard is bounded by the
+/// number of pointers an operation needs at once, plus the pointers returned to the caller, which
+/// stay protected until the guard is dropped.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HazardPointers;
+
+/// Scope of protection of [`HazardPointers`]: the shields protecting the pointers loaded so far.
+#[derive(Debug, Default)]
+pub struct HazardGuard {
+    /// The shields and the addresses they protect. A shield protecting null is free.
+    shields: RefCell<Vec<(Shield<'static>, usize)>>,
+}
+
+impl HazardGuard {
+    /// Runs `f` with a free shield, and records the address it returns as protected.
+    fn with_free_shield(&self, f: impl FnOnce(&Shield<'_>) -> usize) {
+        let mut shields = self.shields.borrow_mut();
+        let index = match shields.iter().position(|(_, address)| *address == 0) {
+            Some(index) => index,
+            None => {
+                shields.push((Shield::default(), 0));
+                shields.len() - 1
+            }
+        };
+        let (shield, address) = &mut shields[index];
+        *address = f(shield);
+    }
+
+    /// Returns the number of shields of the guard, including the free ones.
+    pub fn shields(&self) -> usize {
+        self.shields.borrow().len()
+    }
+}
+
+unsafe impl Reclaimer for HazardPointers {
+    type Guard = HazardGuard;
+
+    fn pin() -> HazardGuard {
+        HazardGuard::default()
+    }
+
+    fn protect<'g, T>(src: &Atomic<T>, order: Ordering, guard: &'g HazardGuard) -> Shared<'g, T> {
+        // SAFETY: the pointer is not dereferenced until it is validated below.
+        let unprotected = unsafe { unprotected() };
+        let mut pointer = src.load(Ordering::Relaxed, unprotected);
+        guard.with_free_shield(|shield| loop {
+            shield.set(pointer.as_raw().cast_mut());
+            // Pairs with the fence in `collect()`, so that either it sees the hazard, or we see
+            // that `src` no longer points to `pointer`.
+            membarrier::light();
+            let current = src.load(order, unprotected);
+            if current == pointer {
+                return pointer.as_raw() as usize;
+            }
+            pointer = current;
+        });
+        pointer
+    }
+
+    fn protect_owned<T>(owned: Owned<T>, guard: &HazardGuard) -> Shared<'_, T> {
+        // SAFETY: `owned` is not shared yet, so it is protected as soon as the shield is set.
+        let shared = owned.into_shared(unsafe { unprotected() });
+        guard.with_free_shield(|shield| {
+            shield.set(shared.as_raw().cast_mut());
+            shared.as_raw() as usize
+        });
+        shared
+    }
+
+    unsafe fn release<T>(ptr: Shared<'_, T>, guard: &HazardGuard) {
+        let address = ptr.as_raw() as usize;
+        if address == 0 {
+            return;
+        }
+        let mut shields = guard.shields.borrow_mut();
+        if let Some((shield, protected)) = shields.iter_mut().find(|(_, a)| *a == address) {
+            shield.clear();
+            *protected = 0;
+        }
+    }
+
+    fn atomic_guard(_: &HazardGuard) -> &Guard {
+        // SAFETY: the pointers loaded with this guard are protected with `protect()` before they
+        // are dereferenced.
+        unsafe { unprotected() }
+    }
+
+    unsafe fn retire<T>(ptr: Shared<'_, T>, _: &HazardGuard) {
+        /// Frees a pointer retired with the type erased.
+        unsafe fn free<T>(data: *mut ()) {
+            drop(Box::from_raw(data.cast::<T>()))
+        }
+
+        // SAFETY: the caller guarantees that `T` can be dropped on any thread, which is what
+        // `T: Send` of `retire_with` ensures.
+        retire_with(ptr.with_tag(0).as_raw().cast_mut().cast::<()>(), free::<T>)
+    }
+}
@@ -1,33 +1,271 @@
 use core::marker::PhantomData;
 #[cfg(not(feature = "check-loom"))]
-use core::sync::atomic::{fence, Ordering};
+use core::sync::atomic::{fence, AtomicPtr, Ordering};
+use core::{mem, ptr};
 #[cfg(feature = "check-loom")]
-use loom::sync::atomic::{fence, Ordering};
+use loom::sync::atomic::{fence, AtomicPtr, Ordering};
+use std::sync::Mutex;
+#[cfg(all(debug_assertions, not(feature = "check-loom")))]
+use std::time::{Duration, Instant};
 
-use super::{HazardBag, HAZARDS};
+use super::{membarrier, HazardBag, HAZARDS};
+
+/// A retired pointer and the function that frees it.
+type Retired = (usize, unsafe fn(*mut ()));
 
 /// Thread-local list of retired pointers.
 #[derive(Debug)]
 pub struct RetiredSet<'s> {
     hazards: &'s HazardBag,
     /// The first element of the pair is the machine representation of the pointer and the second
-    /// is the function pointer to `free::<T>` where `T` is the type of the object.
-    inner: Vec<(usize, unsafe fn(usize))>,
+    /// is the function pointer that frees it, e.g. `free::<T>` where `T` is the type of the object.
+    inner: Vec<Retired>,
+    /// `k` of the reclamation threshold `R = k * H`.
+    factor: usize,
+    /// The lower bound of the reclamation threshold.
+    min_threshold: usize,
+    /// The reclamation threshold, recomputed when it is reached.
+    threshold: usize,
+    /// The pool of the domain that owns `inner`, to which it is returned when the set is dropped.
+    pool: Option<&'s RetiredPool>,
     _marker: PhantomData<*const ()>, // !Send + !Sync
 }
 
+/// Builder of a [`RetiredSet`] with a custom reclamation threshold.
+///
+/// `collect` is triggered when `max(min_threshold, factor * H)` pointers are retired, where `H`
+/// is the number of hazard slots. As a collection frees all but at most `H` pointers, each
+/// collection frees at least `(factor - 1) * H` pointers, so that its O(H) cost is amortized O(1)
+/// per retired pointer if `factor > 1`. A larger factor trades memory for fewer collections.
+#[derive(Debug, Clone, Copy)]
+pub struct RetiredSetBuilder<'s> {
+    hazards: &'s HazardBag,
+    factor: usize,
+    min_threshold: usize,
+}
+
+impl<'s> RetiredSetBuilder<'s> {
+    /// Sets the factor `k` of the reclamation threshold `R = k * H`. Defaults to 2.
+    pub fn factor(mut self, factor: usize) -> Self {
+        self.factor = factor;
+        self
+    }
+
+    /// Sets the lower bound of the reclamation threshold, which matters when there are only a few
+    /// hazard slots. Defaults to 64.
+    pub fn min_threshold(mut self, min_threshold: usize) -> Self {
+        self.min_threshold = min_threshold;
+        self
+    }
+
+    /// Creates the retired pointer list.
+    pub fn build(self) -> RetiredSet<'s> {
+        RetiredSet {
+            hazards: self.hazards,
+            inner: Vec::new(),
+            factor: self.factor,
+            min_threshold: self.min_threshold,
+            threshold: self.min_threshold,
+            pool: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Retired pointer lists owned by a [`HazardPointerDomain`](super::HazardPointerDomain), which
+/// lends them to the threads with [`RetiredSet::from_pool`].
+///
+/// A list is taken with the pointers retired by its previous borrowers, which are freed by the
+/// next `collect` once they are not protected. The lists left in the pool are freed when the pool
+/// is dropped.
+#[derive(Debug, Default)]
+pub(crate) struct RetiredPool {
+    lists: Mutex<Vec<Vec<Retired>>>,
+}
+
+impl RetiredPool {
+    /// Takes a list from the pool, or creates a new one if all lists are in use.
+    fn take(&self) -> Vec<Retired> {
+        self.lists.lock().unwrap().pop().unwrap_or_default()
+    }
+
+    /// Returns a list to the pool.
+    fn put(&self, inner: Vec<Retired>) {
+        self.lists.lock().unwrap().push(inner);
+    }
+}
+
+impl Drop for RetiredPool {
+    /// Frees all pointers in the lists. The domain that owns `self` is being dropped, so no shield
+    /// protects them anymore.
+    fn drop(&mut self) {
+        let lists = self.lists.get_mut().unwrap();
+        for (pointer, free) in lists.drain(..).flatten() {
+            unsafe { free(pointer as *mut ()) };
+        }
+    }
+}
+
+/// Retired pointers left by exited threads, waiting to be adopted by the other threads.
+///
+/// A lock-free stack of batches. Batches are only taken all at once with a `swap`, so there is no
+/// ABA problem.
+#[derive(Debug)]
+pub(crate) struct Orphans {
+    head: AtomicPtr<Batch>,
+}
+
+#[derive(Debug)]
+struct Batch {
+    inner: Vec<Retired>,
+    next: *mut Batch,
+}
+
+impl Orphans {
+    #[cfg(not(feature = "check-loom"))]
+    /// Creates an empty orphan list.
+    pub(crate) const fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    #[cfg(feature = "check-loom")]
+    /// Creates an empty orphan list.
+    pub(crate) fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Hands over retired pointers to the other threads.
+    pub(crate) fn push(&self, inner: Vec<Retired>) {
+        if inner.is_empty() {
+            return;
+        }
+        let batch = Box::into_raw(Box::new(Batch {
+            inner,
+            next: ptr::null_mut(),
+        }));
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            // SAFETY: `batch` is not shared until the CAS succeeds.
+            unsafe { (*batch).next = head };
+            // Release: the adopting thread should see the batch.
+            match self
+                .head
+                .compare_exchange(head, batch, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Takes all the orphaned retired pointers.
+    pub(crate) fn take(&self) -> Vec<Retired> {
+        // Avoid writing to the shared head in the common case where there are no orphans.
+        if self.head.load(Ordering::Relaxed).is_null() {
+            return Vec::new();
+        }
+
+        // Acquire: to see the batches.
+        let mut batch = self.head.swap(ptr::null_mut(), Ordering::Acquire);
+        let mut result = Vec::new();
+        while !batch.is_null() {
+            // SAFETY: the batches were detached by the above swap, so we own them.
+            let b = unsafe { Box::from_raw(batch) };
+            result.extend(b.inner);
+            batch = b.next;
+        }
+        result
+    }
+}
+
+impl Drop for Orphans {
+    /// Frees all orphaned pointers. The hazard bag that owns `self` is being dropped, so no shield
+    /// protects them anymore.
+    fn drop(&mut self) {
+        for (pointer, free) in self.take() {
+            unsafe { free(pointer as *mut ()) };
+        }
+    }
+}
+
 impl<'s> RetiredSet<'s> {
-    /// The max length of retired pointer list. `collect` is triggered when `THRESHOLD` pointers
-    /// are retired.
+    /// The default lower bound of the reclamation threshold. `collect` is triggered when at least
+    /// `THRESHOLD` pointers are retired.
     const THRESHOLD: usize = 64;
 
-    /// Create a new retired pointer list protected by the given `HazardBag`.
+    /// The default factor `k` of the reclamation threshold `R = k * H`.
+    const FACTOR: usize = 2;
+
+    /// How long `drop` waits for the retired pointers to be unprotected before reporting them, in
+    /// debug builds.
+    #[cfg(all(debug_assertions, not(feature = "check-loom")))]
+    const DROP_TIMEOUT: Duration = Duration::from_millis(100);
+
+    /// Create a new retired pointer list protected by the given `HazardBag`, with the default
+    /// reclamation threshold.
     pub fn new(hazards: &'s HazardBag) -> Self {
-        Self {
+        Self::builder(hazards).build()
+    }
+
+    /// Borrows a retired pointer list from `pool`, protected by the given `HazardBag`, with the
+    /// default reclamation threshold. The list is returned to `pool` when the set is dropped,
+    /// instead of being handed over to the other threads.
+    pub(crate) fn from_pool(hazards: &'s HazardBag, pool: &'s RetiredPool) -> Self {
+        let mut set = Self::new(hazards);
+        set.inner = pool.take();
+        set.pool = Some(pool);
+        set
+    }
+
+    /// Returns a builder of a retired pointer list protected by the given `HazardBag`.
+    pub fn builder(hazards: &'s HazardBag) -> RetiredSetBuilder<'s> {
+        RetiredSetBuilder {
             hazards,
-            inner: Vec::new(),
-            _marker: PhantomData,
+            factor: Self::FACTOR,
+            min_threshold: Self::THRESHOLD,
+        }
+    }
+
+    /// Returns whether `collect` should be triggered, i.e. the number of retired pointers reached
+    /// the reclamation threshold. See [`RetiredSetBuilder`].
+    ///
+    /// The threshold is recomputed from the current number of hazard slots only when it is
+    /// reached, so this is amortized O(1).
+    fn should_collect(&mut self) -> bool {
+        if self.inner.len() < self.threshold {
+            return false;
         }
+        self.threshold = self
+            .min_threshold
+            .max(self.factor.saturating_mul(self.hazards.num_slots()));
+        self.inner.len() >= self.threshold
+    }
+
+    /// Returns the number of retired pointers that are not freed yet.
+    pub fn pending(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Prints the retired pointers that are still protected after waiting for `elapsed`.
+    #[cfg(all(debug_assertions, not(feature = "check-loom")))]
+    fn report(&self, elapsed: Duration) {
+        let hazards = self.hazards.all_hazards();
+        let protected = self
+            .inner
+            .iter()
+            .map(|(pointer, _)| *pointer)
+            .filter(|pointer| hazards.contains(pointer))
+            .collect::<Vec<_>>();
+        eprintln!(
+            "RetiredSet: {} retired pointers are still pending after {elapsed:?}, protected: \
+             {protected:#x?} ({:?})",
+            self.inner.len(),
+            self.hazards.stats(),
+        );
     }
 
     /// Retires a pointer.
@@ -40,8 +278,11 @@ impl<'s> RetiredSet<'s> {
     ///
     /// # Note
     ///
-    /// `T: Send` is not required because the retired pointers are not sent to other threads.
-    pub unsafe fn retire<T>(&mut self, pointer: *mut T) {
+    /// `T: Send` is required because the pointers that are still protected when the set is
+    /// dropped are handed over to the other threads, which free them.
+    ///
+    /// Triggers `collect` if the reclamation threshold is reached.
+    pub unsafe fn retire<T: Send>(&mut self, pointer: *mut T) {
         /// Frees a pointer. This function is defined here instead of `collect()` as we know about
         /// the type of `pointer` only at the time of retiring it.
         ///
@@ -51,16 +292,64 @@ impl<'s> RetiredSet<'s> {
         ///   unique ownership to `data`.
         ///
         /// [`Box::from_raw`]: https://doc.rust-lang.org/std/boxed/struct.Box.html#method.from_raw
-        unsafe fn free<T>(data: usize) {
-            drop(Box::from_raw(data as *mut T))
+        unsafe fn free<T>(data: *mut ()) {
+            drop(Box::from_raw(data.cast::<T>()))
         }
 
         todo!()
     }
 
+    /// Retires a pointer that is freed by `free` instead of [`Box::from_raw`], e.g. a pointer to
+    /// memory from a pool or an arena.
+    ///
+    /// # Safety
+    ///
+    /// * `pointer` must be removed from shared memory before calling this function, and must be
+    ///   valid.
+    /// * The same `pointer` should only be retired once.
+    /// * It must be safe to call `free(pointer)` from any thread once `pointer` is not protected.
+    ///
+    /// Triggers `collect` if the reclamation threshold is reached. Like [`RetiredSet::retire`],
+    /// `T: Send` is required as `free` may be called by another thread.
+    ///
+    /// [`Box::from_raw`]: https://doc.rust-lang.org/std/boxed/struct.Box.html#method.from_raw
+    pub unsafe fn retire_with<T: Send>(&mut self, pointer: *mut T, free: unsafe fn(*mut ())) {
+        self.inner.push((pointer as usize, free));
+        if self.should_collect() {
+            self.collect();
+        }
+    }
+
+    /// Defers the execution of `f` to a later `collect`.
+    ///
+    /// The closure is boxed and retired, and as no shield protects the box, `f` is executed by the
+    /// next `collect`. As the retired pointers of an exiting thread are handed over to the other
+    /// threads, `f` may be executed by another thread.
+    pub fn defer<F: FnOnce() + Send + 'static>(&mut self, f: F) {
+        /// Executes the boxed closure.
+        ///
+        /// # Safety
+        ///
+        /// `data` must be a `Box<F>` that is not executed yet.
+        unsafe fn call<F: FnOnce()>(data: *mut ()) {
+            Box::from_raw(data.cast::<F>())()
+        }
+
+        let data = Box::into_raw(Box::new(f));
+        // SAFETY: `data` is not shared, and is executed once.
+        unsafe { self.retire_with(data, call::<F>) };
+    }
+
     /// Free the pointers that are `retire`d by the current thread and not `protect`ed by any other
     /// threads.
+    ///
+    /// The pointers orphaned by the exited threads are adopted and freed as well.
     pub fn collect(&mut self) {
+        self.inner.extend(self.hazards.orphans.take());
+        self.hazards.compact_if_sparse();
+        // Pairs with the fence in `Shield::validate()`, so that either we see the hazards, or the
+        // readers see that the retired pointers are unlinked.
+        membarrier::heavy();
         todo!()
     }
 }
@@ -75,12 +364,36 @@ impl Default for RetiredSet<'static> {
 #[cfg(not(feature = "check-loom"))]
 impl Drop for RetiredSet<'_> {
     fn drop(&mut self) {
-        // In a production-quality implementation of hazard pointers, the remaining local retired
-        // pointers will be moved to a global list of retired pointers, which are then reclaimed by
-        // the other threads. For pedagogical purposes, here we simply wait for all retired pointers
-        // are no longer protected.
-        while !self.inner.is_empty() {
-            self.collect();
+        // A list borrowed from a domain is returned as is, and the next borrower frees its
+        // pointers.
+        if let Some(pool) = self.pool {
+            pool.put(mem::take(&mut self.inner));
+            return;
+        }
+
+        // The retired pointers that are still protected are handed over to the other threads, so
+        // that the exiting thread does not block on their shields.
+        if self.inner.is_empty() {
+            return;
+        }
+        self.collect();
+
+        // In debug builds, wait for the shields for a while to report the pointers they keep
+        // protecting.
+        #[cfg(debug_assertions)]
+        if !self.inner.is_empty() {
+            let start = Instant::now();
+            while !self.inner.is_empty() && start.elapsed() < Self::DROP_TIMEOUT {
+                std::thread::yield_now();
+                self.collect();
+            }
+            if !self.inner.is_empty() {
+                self.report(start.elapsed());
+            }
+        }
+
+        if !self.inner.is_empty() {
+            self.hazards.orphans.push(mem::take(&mut self.inner));
         }
     }
 }
@@ -88,27 +401,93 @@ impl Drop for RetiredSet<'_> {
 #[cfg(all(test, not(feature = "check-loom")))]
 mod tests {
     use super::{HazardBag, RetiredSet};
-    use std::cell::RefCell;
+    use crate::hazard_pointer::Shield;
     use std::collections::HashSet;
-    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
 
     // retire `THRESHOLD` pointers to trigger collection
     #[test]
     fn retire_threshold_collect() {
-        struct Tester(Rc<RefCell<HashSet<usize>>>, usize);
+        struct Tester(Arc<Mutex<HashSet<usize>>>, usize);
         impl Drop for Tester {
             fn drop(&mut self) {
-                let _ = self.0.borrow_mut().insert(self.1);
+                let _ = self.0.lock().unwrap().insert(self.1);
             }
         }
         let hazards = HazardBag::new();
         let mut retires = RetiredSet::new(&hazards);
-        let freed = Rc::new(RefCell::new(HashSet::new()));
+        let freed = Arc::new(Mutex::new(HashSet::new()));
         for i in 0..RetiredSet::THRESHOLD {
             unsafe { retires.retire(Box::leak(Box::new(Tester(freed.clone(), i)))) };
         }
-        let freed = Rc::try_unwrap(freed).unwrap().into_inner();
+        let freed = Arc::try_unwrap(freed).unwrap().into_inner().unwrap();
 
         assert_eq!(freed, (0..RetiredSet::THRESHOLD).collect())
     }
+
+    // the threshold is the given lower bound when there are only a few hazard slots
+    #[test]
+    fn builder_threshold() {
+        unsafe fn noop(_: *mut ()) {}
+        let hazards = HazardBag::new();
+        let mut retires = RetiredSet::builder(&hazards)
+            .factor(4)
+            .min_threshold(8)
+            .build();
+        for _ in 0..7 {
+            retires.inner.push((0, noop));
+            assert!(!retires.should_collect());
+        }
+        retires.inner.push((0, noop));
+        assert!(retires.should_collect());
+        retires.inner.clear();
+    }
+
+    // the custom deleters and the deferred closures are executed by `collect`
+    #[test]
+    fn retire_with_defer() {
+        static FREED: AtomicUsize = AtomicUsize::new(0);
+        unsafe fn release(data: *mut ()) {
+            let _ = FREED.fetch_add(*data.cast::<usize>(), Ordering::Relaxed);
+        }
+        let hazards = HazardBag::new();
+        let mut retires = RetiredSet::new(&hazards);
+        let mut pool = [1usize, 2];
+        unsafe { retires.retire_with(&mut pool[0], release) };
+        unsafe { retires.retire_with(&mut pool[1], release) };
+        retires.defer(|| {
+            let _ = FREED.fetch_add(4, Ordering::Relaxed);
+        });
+        retires.collect();
+        assert_eq!(FREED.load(Ordering::Relaxed), 7);
+    }
+
+    // the pointers left by a dropped set are adopted by another set's `collect`
+    #[test]
+    fn drop_orphans_collect() {
+        struct Tester(Arc<Mutex<HashSet<usize>>>, usize);
+        impl Drop for Tester {
+            fn drop(&mut self) {
+                let _ = self.0.lock().unwrap().insert(self.1);
+            }
+        }
+        let hazards = HazardBag::new();
+        let freed = Arc::new(Mutex::new(HashSet::new()));
+        let shield = Shield::new(&hazards);
+        {
+            let mut retires = RetiredSet::new(&hazards);
+            let pointer = Box::leak(Box::new(Tester(freed.clone(), 0)));
+            shield.set(pointer);
+            unsafe { retires.retire(pointer) };
+            // does not block although `pointer` is protected
+        }
+        assert!(freed.lock().unwrap().is_empty());
+
+        drop(shield);
+        let mut retires = RetiredSet::new(&hazards);
+        retires.collect();
+        assert_eq!(*freed.lock().unwrap(), [0].into_iter().collect());
+        assert_eq!(retires.pending(), 0);
+    }
 }
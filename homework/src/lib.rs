@@ -14,6 +14,8 @@ mod adt;
 mod arc;
 mod elim_stack;
 mod hash_table;
+#[cfg(not(feature = "check-loom"))]
+pub mod hazard_era;
 pub mod hazard_pointer;
 pub mod hello_server;
 mod linked_list;
@@ -27,6 +29,6 @@ pub use adt::{
 };
 pub use arc::Arc;
 pub use elim_stack::ElimStack;
-pub use hash_table::{GrowableArray, SplitOrderedList};
+pub use hash_table::{GrowableArray, GrowableArrayIter, SplitOrderedList, SplitOrderedListIter};
 pub use linked_list::LinkedList;
 pub use list_set::{FineGrainedListSet, OptimisticFineGrainedListSet};
@@ -174,3 +174,136 @@ fn log_concurrent() {
     const STEPS: usize = 4096 * 12;
     map::log_concurrent::<u32, NonblockingConcurrentMap<_, _, ArrayMap<usize>>>(THREADS, STEPS);
 }
+
+#[test]
+fn truncate() {
+    let mut array = GrowableArray::<usize>::new();
+    let indices = [0, 5, 1 << 12, 1 << 24];
+    {
+        let guard = pin();
+        for index in indices {
+            array
+                .get(index, &guard)
+                .store(Owned::new(index), Ordering::Relaxed);
+        }
+    }
+
+    let take = |array: &GrowableArray<usize>, index| {
+        let guard = pin();
+        let ptr = array
+            .get(index, &guard)
+            .swap(Shared::null(), Ordering::Relaxed, &guard);
+        (!ptr.is_null()).then(|| *unsafe { ptr.into_owned() })
+    };
+    assert_eq!(take(&array, 1 << 24), Some(1 << 24));
+    assert_eq!(take(&array, 1 << 12), Some(1 << 12));
+    array.truncate(8);
+
+    // the elements below `len` are kept, and the released indices are null
+    assert_eq!(take(&array, 5), Some(5));
+    assert_eq!(take(&array, 1 << 12), None);
+    assert_eq!(take(&array, 1 << 24), None);
+    assert_eq!(take(&array, 0), Some(0));
+}
+
+#[test]
+fn truncate_sparse() {
+    let mut array = GrowableArray::<usize>::new();
+    {
+        let guard = pin();
+        let slot = array.get(1 << 24, &guard);
+        slot.store(Owned::new(1 << 24), Ordering::Relaxed);
+        drop(unsafe {
+            slot.swap(Shared::null(), Ordering::Relaxed, &guard)
+                .into_owned()
+        });
+    }
+    assert_eq!(array.height(), 3);
+
+    // if the segments of the low indices were never allocated, the tree becomes empty
+    array.truncate(8);
+    assert!(array.height() <= 1);
+    assert_eq!(array.capacity(), (1 << 10) * array.height());
+
+    // and grows from the bottom again
+    let guard = pin();
+    array.get(3, &guard).store(Owned::new(3), Ordering::Relaxed);
+    assert_eq!(array.height(), 1);
+    let ptr = array
+        .get(3, &guard)
+        .swap(Shared::null(), Ordering::Relaxed, &guard);
+    assert_eq!(*unsafe { ptr.into_owned() }, 3);
+}
+
+#[test]
+fn release() {
+    let array = GrowableArray::<usize>::new();
+    let indices = [0, 5, 1 << 12, 1 << 24];
+    let guard = pin();
+    for index in indices {
+        array
+            .get(index, &guard)
+            .store(Owned::new(index), Ordering::Relaxed);
+    }
+    let take = |index| {
+        let ptr = array
+            .get(index, &guard)
+            .swap(Shared::null(), Ordering::Relaxed, &guard);
+        (!ptr.is_null()).then(|| *unsafe { ptr.into_owned() })
+    };
+    assert_eq!(take(1 << 24), Some(1 << 24));
+    assert_eq!(take(1 << 12), Some(1 << 12));
+
+    // a slot obtained before the release stays valid while pinned
+    let released = array.get(1 << 12, &guard);
+    array.release(8, &guard);
+    assert!(released.load(Ordering::Relaxed, &guard).is_null());
+
+    // the height is kept, the elements below `len` are kept, and the released indices are null
+    assert_eq!(array.height(), 3);
+    let indices = array
+        .iter(&guard)
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    assert_eq!(indices, [0, 5]);
+    assert_eq!(take(5), Some(5));
+    assert_eq!(take(1 << 12), None);
+    assert_eq!(take(0), Some(0));
+}
+
+#[test]
+fn iter() {
+    let mut array = GrowableArray::<usize>::new();
+    assert_eq!(array.height(), 0);
+    assert_eq!(array.capacity(), 0);
+
+    let indices = [0, 5, 1023, 1 << 12, (1 << 20) + 7];
+    let guard = pin();
+    for index in indices {
+        array
+            .get(index, &guard)
+            .store(Owned::new(index), Ordering::Relaxed);
+    }
+    assert_eq!(array.height(), 3);
+    assert_eq!(array.capacity(), 1 << 30);
+
+    // only the populated slots are yielded, in the order of the indices
+    let _ = array.get(1 << 24, &guard);
+    let entries = array
+        .iter(&guard)
+        .map(|(index, slot)| {
+            (index, *unsafe {
+                slot.load(Ordering::Relaxed, &guard).deref()
+            })
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(entries, indices.map(|index| (index, index)));
+
+    for (_, slot) in array.iter(&guard) {
+        let ptr = slot.swap(Shared::null(), Ordering::Relaxed, &guard);
+        drop(unsafe { ptr.into_owned() });
+    }
+    drop(guard);
+    array.truncate(0);
+    assert_eq!(array.height(), 0);
+}
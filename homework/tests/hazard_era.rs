@@ -0,0 +1,46 @@
+#![cfg(not(feature = "check-loom"))]
+
+use core::sync::atomic::{AtomicPtr, Ordering::*};
+use std::thread::scope;
+
+use cs431_homework::hazard_era::{alloc, collect, retire, Shield};
+
+/// Readers traverse the cells while writers replace and retire them.
+#[test]
+fn replace_read() {
+    const THREADS: usize = 8;
+    const CELLS: usize = 16;
+    const ITER: usize = 1024 * 16;
+
+    let cells = (0..CELLS)
+        .map(|i| AtomicPtr::new(alloc([i; 4])))
+        .collect::<Vec<_>>();
+    scope(|s| {
+        for t in 0..THREADS {
+            let cells = &cells;
+            let _unused = s.spawn(move || {
+                let shield = Shield::default();
+                for i in 0..ITER {
+                    let cell = &cells[(t + i) % CELLS];
+                    if t % 2 == 0 {
+                        let new = alloc([(t + i) % CELLS; 4]);
+                        let old = cell.swap(new, AcqRel);
+                        unsafe { retire(old) };
+                    } else {
+                        for cell in cells {
+                            // The shield protects only the latest cell, so the value is copied
+                            // before moving on.
+                            let value = unsafe { *shield.protect(cell) };
+                            assert!(value.iter().all(|v| *v == value[0]));
+                        }
+                        shield.clear();
+                    }
+                }
+                collect();
+            });
+        }
+    });
+    for cell in cells {
+        unsafe { retire(cell.into_inner()) };
+    }
+}
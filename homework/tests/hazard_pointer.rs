@@ -6,8 +6,7 @@ use core::sync::atomic::{AtomicPtr, Ordering::*};
 #[cfg(feature = "check-loom")]
 use loom::sync::atomic::{AtomicPtr, Ordering::*};
 
-use cs431_homework::hazard_pointer::{collect, retire, Shield};
-use queue::Queue;
+use cs431_homework::hazard_pointer::{collect, retire, Queue, Shield};
 use stack::Stack;
 use std::thread::scope;
 
@@ -161,6 +160,170 @@ fn stack_queue() {
     assert!(stack.try_pop().is_none());
 }
 
+#[test]
+fn queue_concurrent() {
+    const THREADS: usize = 4;
+    const ITER: usize = 1024 * 16;
+
+    let queue = Queue::default();
+    let mut popped = scope(|s| {
+        for t in 0..THREADS {
+            let queue = &queue;
+            let _unused = s.spawn(move || {
+                for i in 0..ITER {
+                    queue.push((t, i));
+                }
+            });
+        }
+
+        let handles = (0..THREADS)
+            .map(|_| {
+                s.spawn(|| {
+                    let mut popped = Vec::new();
+                    let mut last = [None; THREADS];
+                    while popped.len() < ITER {
+                        let Some((t, i)) = queue.try_pop() else {
+                            std::thread::yield_now();
+                            continue;
+                        };
+                        // The elements pushed by a thread are popped in order.
+                        assert!(last[t] < Some(i));
+                        last[t] = Some(i);
+                        popped.push((t, i));
+                        collect();
+                    }
+                    popped
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    assert!(queue.try_pop().is_none());
+    popped.sort_unstable();
+    let expected = (0..THREADS)
+        .flat_map(|t| (0..ITER).map(move |i| (t, i)))
+        .collect::<Vec<_>>();
+    assert_eq!(popped, expected);
+}
+
+#[cfg(not(feature = "check-loom"))]
+mod list_set {
+    use cs431_homework::hazard_pointer::ListSet;
+    use cs431_homework::test::adt::set;
+    use cs431_homework::ConcurrentSet;
+
+    #[test]
+    fn smoke() {
+        let set = ListSet::new();
+        assert!(set.insert(1));
+        assert!(set.insert(3));
+        assert!(!set.insert(1));
+        assert!(set.insert(2));
+        assert!(set.contains(&2));
+        assert!(set.remove(&2));
+        assert!(!set.contains(&2));
+        assert!(!set.remove(&2));
+        assert!(set.contains(&1));
+        assert!(set.contains(&3));
+    }
+
+    #[test]
+    fn stress_sequential() {
+        const STEPS: usize = 4096;
+        set::stress_sequential::<u8, ListSet<u8>>(STEPS);
+    }
+
+    #[test]
+    fn stress_concurrent() {
+        const THREADS: usize = 16;
+        const STEPS: usize = 4096 * 16;
+        set::stress_concurrent::<u8, ListSet<u8>>(THREADS, STEPS);
+    }
+
+    #[test]
+    fn log_concurrent() {
+        const THREADS: usize = 16;
+        const STEPS: usize = 4096 * 16;
+        set::log_concurrent::<u8, ListSet<u8>>(THREADS, STEPS);
+    }
+}
+
+#[cfg(not(feature = "check-loom"))]
+#[test]
+fn reclaimer() {
+    use cs431::lockfree::{List, Queue as LockFreeQueue, Stack as LockFreeStack};
+    use cs431::reclaim::Reclaimer;
+    use cs431_homework::hazard_pointer::HazardPointers;
+
+    const THREADS: usize = 8;
+    const ITER: usize = 1024 * 16;
+
+    let stack = LockFreeStack::<usize, HazardPointers>::with_elimination_in();
+    let queue = LockFreeQueue::<usize, HazardPointers>::default();
+    let list = List::<usize, usize, HazardPointers>::default();
+    scope(|s| {
+        for t in 0..THREADS {
+            let (stack, queue, list) = (&stack, &queue, &list);
+            let _unused = s.spawn(move || {
+                for i in 0..ITER {
+                    let key = i % 64;
+                    stack.push(i);
+                    assert!(stack.pop().is_some());
+
+                    let guard = &HazardPointers::pin();
+                    queue.push(i, guard);
+                    assert!(queue.try_pop(guard).is_some());
+                    if t % 2 == 0 {
+                        let _ = list.harris_michael_insert(key, i, guard);
+                    } else {
+                        let _ = list.harris_michael_delete(&key, guard);
+                    }
+                    let _ = list.harris_michael_lookup(&key, guard);
+                }
+                collect();
+            });
+        }
+    });
+    assert!(stack.pop().is_none());
+}
+
+#[cfg(not(feature = "check-loom"))]
+#[test]
+fn reclaimer_shields() {
+    use cs431::lockfree::{List, Queue as LockFreeQueue};
+    use cs431::reclaim::Reclaimer;
+    use cs431_homework::hazard_pointer::HazardPointers;
+
+    const COUNT: usize = 128;
+
+    let list = List::<usize, usize, HazardPointers>::default();
+    for i in 0..COUNT {
+        assert!(list.harris_michael_insert(i, i, &HazardPointers::pin()));
+    }
+    // The traversal releases the nodes it passes by, so at most the previous, current and next
+    // nodes are protected at once.
+    let guard = &HazardPointers::pin();
+    assert_eq!(
+        list.harris_michael_lookup(&(COUNT - 1), guard),
+        Some(&(COUNT - 1))
+    );
+    assert!(guard.shields() <= 3, "shields: {}", guard.shields());
+
+    // The shields are reused across the operations with the same guard.
+    let queue = LockFreeQueue::<usize, HazardPointers>::default();
+    let guard = &HazardPointers::pin();
+    for i in 0..COUNT {
+        queue.push(i, guard);
+        assert_eq!(queue.try_pop(guard), Some(i));
+    }
+    assert!(guard.shields() <= 2, "shields: {}", guard.shields());
+    collect();
+}
+
 mod sync {
     use core::ptr;
     use cs431_homework::hazard_pointer::*;
@@ -292,7 +455,7 @@ mod stack {
         }
     }
 
-    impl<T> Stack<T> {
+    impl<T: Send> Stack<T> {
         pub fn push(&self, t: T) {
             let new = Box::leak(Box::new(Node {
                 data: ManuallyDrop::new(t),
@@ -347,157 +510,3 @@ mod stack {
         }
     }
 }
-
-mod queue {
-    use core::mem::MaybeUninit;
-    use core::ptr;
-
-    #[cfg(not(feature = "check-loom"))]
-    use core::sync::atomic::{AtomicPtr, Ordering::*};
-    #[cfg(feature = "check-loom")]
-    use loom::sync::atomic::{AtomicPtr, Ordering::*};
-
-    use cs431_homework::hazard_pointer::{retire, Shield};
-
-    /// Michael-Scott queue.
-    #[derive(Debug)]
-    pub struct Queue<T> {
-        head: AtomicPtr<Node<T>>,
-        tail: AtomicPtr<Node<T>>,
-    }
-
-    #[derive(Debug)]
-    struct Node<T> {
-        data: MaybeUninit<T>,
-        next: AtomicPtr<Node<T>>,
-    }
-
-    unsafe impl<T: Send> Sync for Queue<T> {}
-    unsafe impl<T: Send> Send for Queue<T> {}
-
-    impl<T> Default for Queue<T> {
-        fn default() -> Self {
-            let q = Self {
-                head: AtomicPtr::new(ptr::null_mut()),
-                tail: AtomicPtr::new(ptr::null_mut()),
-            };
-            let sentinel = Box::leak(Box::new(Node {
-                data: MaybeUninit::uninit(),
-                next: AtomicPtr::new(ptr::null_mut()),
-            }));
-            q.head.store(sentinel, Relaxed);
-            q.tail.store(sentinel, Relaxed);
-            q
-        }
-    }
-
-    impl<T> Queue<T> {
-        pub fn push(&self, t: T) {
-            let new = Box::leak(Box::new(Node {
-                data: MaybeUninit::new(t),
-                next: AtomicPtr::new(ptr::null_mut()),
-            }));
-            let shield = Shield::default();
-
-            loop {
-                let tail = shield.protect(&self.tail);
-                // SAFETY
-                // 1. queue's `tail` is always valid as it will be CASed with valid nodes only.
-                // 2. `tail` is protected & validated.
-                let tail_ref = unsafe { &*tail };
-
-                let next = tail_ref.next.load(Acquire);
-                if !next.is_null() {
-                    let _ = self.tail.compare_exchange(tail, next, Release, Relaxed);
-                    continue;
-                }
-
-                if tail_ref
-                    .next
-                    .compare_exchange(ptr::null_mut(), new, Release, Relaxed)
-                    .is_ok()
-                {
-                    let _ = self.tail.compare_exchange(tail, new, Release, Relaxed);
-                    break;
-                }
-            }
-        }
-
-        pub fn try_pop(&self) -> Option<T> {
-            let head_shield = Shield::default();
-            let next_shield = Shield::default();
-            let mut head = self.head.load(Acquire);
-            loop {
-                if let Err(new) = head_shield.try_protect(head, &self.head) {
-                    head = new;
-                    continue;
-                }
-                // SAFETY:
-                // 1. queue's `head` is always valid as it will be CASed with valid nodes only.
-                // 2. `head` is protected & validated.
-                let head_ref = unsafe { &*head };
-
-                let next = head_ref.next.load(Acquire);
-                if next.is_null() {
-                    return None;
-                }
-                next_shield.set(next);
-                let next_ref = match Shield::validate(head, &self.head) {
-                    Ok(_) => {
-                        // SAFETY:
-                        // 1. If `next` was not null, then it must be a valid node that another
-                        //    thread has `push()`ed.
-                        // 2. Validation: If `head` is not retired, then `next` is not retired. So
-                        //    re-validating `head` also validates `next.
-                        unsafe { &*next }
-                    }
-                    Err(new) => {
-                        next_shield.clear();
-                        head = new;
-                        continue;
-                    }
-                };
-
-                let tail = self.tail.load(Relaxed);
-                if tail == head {
-                    let _ = self.tail.compare_exchange(tail, next, Release, Relaxed);
-                }
-
-                if self
-                    .head
-                    .compare_exchange(head, next, Release, Relaxed)
-                    .is_ok()
-                {
-                    let result = unsafe { next_ref.data.assume_init_read() };
-                    unsafe { retire(head) };
-                    return Some(result);
-                }
-            }
-        }
-    }
-
-    impl<T> Drop for Queue<T> {
-        #[cfg(feature = "check-loom")]
-        fn drop(&mut self) {
-            let sentinel = unsafe { Box::from_raw(self.head.load(Relaxed)) };
-
-            let mut curr = sentinel.next.load(Relaxed);
-            while !curr.is_null() {
-                let curr_ref = unsafe { Box::from_raw(curr) };
-                drop(unsafe { curr_ref.data.assume_init() });
-                curr = curr_ref.next.load(Relaxed);
-            }
-        }
-        #[cfg(not(feature = "check-loom"))]
-        fn drop(&mut self) {
-            let sentinel = unsafe { Box::from_raw(*self.head.get_mut()) };
-
-            let mut curr = sentinel.next.into_inner();
-            while !curr.is_null() {
-                let curr_ref = unsafe { Box::from_raw(curr) };
-                drop(unsafe { curr_ref.data.assume_init() });
-                curr = curr_ref.next.into_inner();
-            }
-        }
-    }
-}
@@ -1,11 +1,14 @@
+use core::hash::{BuildHasherDefault, Hasher};
 use crossbeam_epoch as epoch;
+use std::collections::HashMap;
+use std::thread;
 
 use cs431_homework::test::adt::map;
 use cs431_homework::{NonblockingConcurrentMap, NonblockingMap, SplitOrderedList};
 
 #[test]
 pub fn smoke() {
-    let list = SplitOrderedList::<usize>::new();
+    let list = SplitOrderedList::<usize, usize>::new();
 
     let guard = epoch::pin();
 
@@ -31,7 +34,7 @@ fn stress_sequential() {
     const STEPS: usize = 4096;
     map::stress_concurrent_sequential::<
         usize,
-        NonblockingConcurrentMap<_, _, SplitOrderedList<usize>>,
+        NonblockingConcurrentMap<_, _, SplitOrderedList<usize, usize>>,
     >(STEPS);
 }
 
@@ -39,7 +42,7 @@ fn stress_sequential() {
 fn lookup_concurrent() {
     const THREADS: usize = 4;
     const STEPS: usize = 4096;
-    map::lookup_concurrent::<usize, NonblockingConcurrentMap<_, _, SplitOrderedList<usize>>>(
+    map::lookup_concurrent::<usize, NonblockingConcurrentMap<_, _, SplitOrderedList<usize, usize>>>(
         THREADS, STEPS,
     );
 }
@@ -48,7 +51,7 @@ fn lookup_concurrent() {
 fn insert_concurrent() {
     const THREADS: usize = 8;
     const STEPS: usize = 4096 * 4;
-    map::insert_concurrent::<usize, NonblockingConcurrentMap<_, _, SplitOrderedList<usize>>>(
+    map::insert_concurrent::<usize, NonblockingConcurrentMap<_, _, SplitOrderedList<usize, usize>>>(
         THREADS, STEPS,
     );
 }
@@ -57,7 +60,7 @@ fn insert_concurrent() {
 fn stress_concurrent() {
     const THREADS: usize = 16;
     const STEPS: usize = 4096 * 512;
-    map::stress_concurrent::<usize, NonblockingConcurrentMap<_, _, SplitOrderedList<usize>>>(
+    map::stress_concurrent::<usize, NonblockingConcurrentMap<_, _, SplitOrderedList<usize, usize>>>(
         THREADS, STEPS,
     );
 }
@@ -66,7 +69,125 @@ fn stress_concurrent() {
 fn log_concurrent() {
     const THREADS: usize = 16;
     const STEPS: usize = 4096 * 64;
-    map::log_concurrent::<usize, NonblockingConcurrentMap<_, _, SplitOrderedList<usize>>>(
+    map::log_concurrent::<usize, NonblockingConcurrentMap<_, _, SplitOrderedList<usize, usize>>>(
         THREADS, STEPS,
     );
 }
+
+#[test]
+fn shrink() {
+    const KEYS: usize = 4096;
+    let list = SplitOrderedList::<usize, usize>::new();
+
+    // the deletions shrink the table and unlink the unused buckets as they go
+    let guard = epoch::pin();
+    for key in 0..KEYS {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+    for key in 1..KEYS {
+        assert_eq!(list.delete(&key, &guard), Ok(&key));
+    }
+
+    assert_eq!(list.lookup(&0, &guard), Some(&0));
+    for key in 1..KEYS {
+        assert_eq!(list.lookup(&key, &guard), None);
+    }
+    // grows again
+    for key in 1..KEYS {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+    for key in 0..KEYS {
+        assert_eq!(list.lookup(&key, &guard), Some(&key));
+    }
+}
+
+#[test]
+fn shrink_concurrent() {
+    const THREADS: usize = 8;
+    const KEYS: usize = 1024;
+    const ROUNDS: usize = 16;
+    let list = SplitOrderedList::<usize, usize>::new();
+
+    // the table grows and shrinks repeatedly while the other threads operate on it
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            let list = &list;
+            let _unused = s.spawn(move || {
+                let keys = (0..KEYS).map(|i| i * THREADS + t);
+                for _ in 0..ROUNDS {
+                    let guard = epoch::pin();
+                    for key in keys.clone() {
+                        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+                    }
+                    for key in keys.clone() {
+                        assert_eq!(list.lookup(&key, &guard), Some(&key));
+                    }
+                    for key in keys.clone() {
+                        assert_eq!(list.delete(&key, &guard), Ok(&key));
+                    }
+                }
+            });
+        }
+    });
+
+    let guard = epoch::pin();
+    assert!(list.is_empty());
+    assert_eq!(list.iter(&guard).count(), 0);
+}
+
+/// Hashes every key to the same value.
+#[derive(Debug, Default)]
+struct CollidingHasher;
+
+impl Hasher for CollidingHasher {
+    fn finish(&self) -> u64 {
+        // the reserved most significant bit is set as well
+        u64::MAX
+    }
+
+    fn write(&mut self, _: &[u8]) {}
+}
+
+#[test]
+fn hash_collisions() {
+    const KEYS: usize = 64;
+    let list = SplitOrderedList::<String, usize, BuildHasherDefault<CollidingHasher>>::default();
+    let guard = epoch::pin();
+
+    for i in 0..KEYS {
+        assert_eq!(list.insert(&i.to_string(), i, &guard), Ok(()));
+    }
+    assert_eq!(list.insert(&0.to_string(), 0, &guard), Err(0));
+    for i in (0..KEYS).step_by(2) {
+        assert_eq!(list.delete(&i.to_string(), &guard), Ok(&i));
+    }
+    for i in 0..KEYS {
+        let expected = (i % 2 == 1).then_some(i);
+        assert_eq!(list.lookup(&i.to_string(), &guard), expected.as_ref());
+    }
+}
+
+#[test]
+fn iter_len() {
+    const KEYS: usize = 256;
+    let list = SplitOrderedList::<usize, usize>::new();
+    let guard = epoch::pin();
+    assert!(list.is_empty());
+
+    for key in 0..KEYS {
+        assert_eq!(list.insert(&key, key * 10, &guard), Ok(()));
+    }
+    for key in (0..KEYS).step_by(2) {
+        assert_eq!(list.delete(&key, &guard), Ok(&(key * 10)));
+    }
+    assert_eq!(list.len(), KEYS / 2);
+
+    let entries = list
+        .iter(&guard)
+        .map(|(k, v)| (*k, *v))
+        .collect::<HashMap<_, _>>();
+    assert_eq!(entries.len(), KEYS / 2);
+    assert!((1..KEYS)
+        .step_by(2)
+        .all(|k| entries.get(&k) == Some(&(k * 10))));
+}
@@ -6,3 +6,4 @@
 
 pub mod lock;
 pub mod lockfree;
+pub mod reclaim;
@@ -0,0 +1,437 @@
+//! Unbounded multi-producer single-consumer channel on top of [`Queue`].
+//!
+//! The messages are passed through a lock-free queue. The receiver parks when the queue is empty,
+//! and the senders unpark it only when it announced that it is parked, so that a `send()` on the
+//! fast path does not take any lock.
+
+use core::cell::Cell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering};
+use core::time::Duration;
+use std::error;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, Thread};
+use std::time::Instant;
+
+use crossbeam_epoch::pin;
+
+use super::Queue;
+
+#[derive(Debug)]
+struct Channel<T> {
+    queue: Queue<T>,
+    /// Number of live senders.
+    senders: AtomicUsize,
+    /// Whether the receiver is dropped.
+    disconnected: AtomicBool,
+    /// Whether the receiver is (about to be) parked.
+    waiting: AtomicBool,
+    /// The thread of the receiver that is waiting.
+    waiter: Mutex<Option<Thread>>,
+}
+
+/// The sending side of a channel.
+#[derive(Debug)]
+pub struct Sender<T> {
+    chan: Arc<Channel<T>>,
+}
+
+/// The receiving side of a channel.
+///
+/// There is only one receiver, so it can be sent to other threads but is not `Sync`: the channel
+/// remembers only one parked receiver thread to wake up.
+///
+/// ```compile_fail
+/// use cs431::lockfree::channel::Receiver;
+///
+/// fn assert_sync<T: Sync>() {}
+/// assert_sync::<Receiver<usize>>();
+/// ```
+#[derive(Debug)]
+pub struct Receiver<T> {
+    chan: Arc<Channel<T>>,
+    _marker: PhantomData<Cell<()>>, // !Sync
+}
+
+/// The message could not be sent because the receiver is dropped.
+///
+/// The message is returned back.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct SendError<T>(pub T);
+
+/// The channel is empty and all senders are dropped.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RecvError;
+
+/// Error of [`Receiver::try_recv`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TryRecvError {
+    /// The channel is empty.
+    Empty,
+    /// The channel is empty and all senders are dropped.
+    Disconnected,
+}
+
+/// Error of [`Receiver::recv_timeout`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RecvTimeoutError {
+    /// No message arrived before the timeout.
+    Timeout,
+    /// The channel is empty and all senders are dropped.
+    Disconnected,
+}
+
+/// Blocking iterator over the messages, created by [`Receiver::iter`].
+#[derive(Debug)]
+pub struct Iter<'a, T> {
+    receiver: &'a Receiver<T>,
+}
+
+/// Non-blocking iterator over the messages, created by [`Receiver::try_iter`].
+#[derive(Debug)]
+pub struct TryIter<'a, T> {
+    receiver: &'a Receiver<T>,
+}
+
+/// Blocking owning iterator over the messages.
+#[derive(Debug)]
+pub struct IntoIter<T> {
+    receiver: Receiver<T>,
+}
+
+unsafe impl<T: Send> Send for Sender<T> {}
+unsafe impl<T: Send> Sync for Sender<T> {}
+
+/// Creates an unbounded multi-producer single-consumer channel.
+pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+    let chan = Arc::new(Channel {
+        queue: Queue::new(),
+        senders: AtomicUsize::new(1),
+        disconnected: AtomicBool::new(false),
+        waiting: AtomicBool::new(false),
+        waiter: Mutex::new(None),
+    });
+    (
+        Sender { chan: chan.clone() },
+        Receiver {
+            chan,
+            _marker: PhantomData,
+        },
+    )
+}
+
+impl<T> Channel<T> {
+    /// Wakes up the receiver if it is parked.
+    fn notify(&self) {
+        // SeqCst: pairs with the fence in `Receiver::wait()`. Either the receiver sees our update,
+        // or we see that it is waiting.
+        fence(Ordering::SeqCst);
+        if self.waiting.swap(false, Ordering::Relaxed) {
+            if let Some(thread) = self.waiter.lock().unwrap().as_ref() {
+                thread.unpark();
+            }
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Sends a message.
+    ///
+    /// Fails if the receiver is dropped.
+    pub fn send(&self, t: T) -> Result<(), SendError<T>> {
+        if self.chan.disconnected.load(Ordering::Relaxed) {
+            return Err(SendError(t));
+        }
+        self.chan.queue.push(t, &pin());
+        self.chan.notify();
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        let _ = self.chan.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        // AcqRel: the receiver that sees the last decrement should see all the messages.
+        if self.chan.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.chan.notify();
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Receives a message if there is one.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        if let Some(t) = self.chan.queue.try_pop(&pin()) {
+            return Ok(t);
+        }
+        if self.chan.senders.load(Ordering::Acquire) != 0 {
+            return Err(TryRecvError::Empty);
+        }
+        // The messages sent before the last sender is dropped are visible now.
+        self.chan
+            .queue
+            .try_pop(&pin())
+            .ok_or(TryRecvError::Disconnected)
+    }
+
+    /// Blocks until a message arrives.
+    ///
+    /// Fails if the channel is empty and all senders are dropped.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        loop {
+            match self.wait(None) {
+                Ok(t) => return Ok(t),
+                Err(TryRecvError::Empty) => continue,
+                Err(TryRecvError::Disconnected) => return Err(RecvError),
+            }
+        }
+    }
+
+    /// Blocks until a message arrives or the timeout elapses.
+    ///
+    /// Fails if the channel is empty and all senders are dropped.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.wait(Some(deadline)) {
+                Ok(t) => return Ok(t),
+                Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+                Err(TryRecvError::Empty) if Instant::now() >= deadline => {
+                    return Err(RecvTimeoutError::Timeout)
+                }
+                Err(TryRecvError::Empty) => continue,
+            }
+        }
+    }
+
+    /// Tries to receive a message, and parks until notified or the deadline if there is none.
+    fn wait(&self, deadline: Option<Instant>) -> Result<T, TryRecvError> {
+        match self.try_recv() {
+            Err(TryRecvError::Empty) => {}
+            result => return result,
+        }
+
+        *self.chan.waiter.lock().unwrap() = Some(thread::current());
+        self.chan.waiting.store(true, Ordering::Relaxed);
+        // SeqCst: pairs with the fence in `Channel::notify()`.
+        fence(Ordering::SeqCst);
+
+        // Check again, since a message may have arrived before we announced that we are waiting.
+        match self.try_recv() {
+            Err(TryRecvError::Empty) => {}
+            result => {
+                self.chan.waiting.store(false, Ordering::Relaxed);
+                return result;
+            }
+        }
+
+        match deadline {
+            None => thread::park(),
+            Some(deadline) => {
+                thread::park_timeout(deadline.saturating_duration_since(Instant::now()))
+            }
+        }
+        self.chan.waiting.store(false, Ordering::Relaxed);
+        self.try_recv()
+    }
+
+    /// Returns a blocking iterator over the messages, which ends when all senders are dropped.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { receiver: self }
+    }
+
+    /// Returns an iterator over the messages that are currently in the channel.
+    pub fn try_iter(&self) -> TryIter<'_, T> {
+        TryIter { receiver: self }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.chan.disconnected.store(true, Ordering::Relaxed);
+    }
+}
+
+impl<T> Iterator for Iter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl<T> Iterator for TryIter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Receiver<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T> IntoIterator for Receiver<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { receiver: self }
+    }
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SendError(..)")
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("sending on a disconnected channel")
+    }
+}
+
+impl<T> error::Error for SendError<T> {}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("receiving on an empty and disconnected channel")
+    }
+}
+
+impl error::Error for RecvError {}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("receiving on an empty channel"),
+            Self::Disconnected => f.write_str("receiving on an empty and disconnected channel"),
+        }
+    }
+}
+
+impl error::Error for TryRecvError {}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout => f.write_str("timed out waiting on channel"),
+            Self::Disconnected => f.write_str("receiving on an empty and disconnected channel"),
+        }
+    }
+}
+
+impl error::Error for RecvTimeoutError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread::scope;
+
+    #[test]
+    fn send_recv() {
+        let (tx, rx) = unbounded();
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+
+        tx.send(3).unwrap();
+        drop(tx);
+        assert_eq!(rx.recv(), Ok(3));
+        assert_eq!(rx.recv(), Err(RecvError));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Disconnected)
+        );
+    }
+
+    #[test]
+    fn receiver_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Receiver<usize>>();
+    }
+
+    #[test]
+    fn receiver_dropped() {
+        let (tx, rx) = unbounded();
+        drop(rx);
+        assert_eq!(tx.send(1), Err(SendError(1)));
+    }
+
+    #[test]
+    fn mpsc() {
+        const THREADS: usize = 4;
+        const COUNT: usize = 10_000;
+        let (tx, rx) = unbounded();
+
+        scope(|scope| {
+            for t in 0..THREADS {
+                let tx = tx.clone();
+                scope.spawn(move || {
+                    for i in 0..COUNT {
+                        tx.send((t, i)).unwrap();
+                    }
+                });
+            }
+            drop(tx);
+
+            // Messages from the same sender arrive in order, and the iterator ends when all
+            // senders are dropped.
+            let mut next = [0; THREADS];
+            for (t, i) in rx {
+                assert_eq!(next[t], i);
+                next[t] += 1;
+            }
+            assert_eq!(next, [COUNT; THREADS]);
+        });
+    }
+
+    #[test]
+    fn recv_wakeup() {
+        let (tx, rx) = unbounded();
+        scope(|scope| {
+            scope.spawn(move || {
+                for i in 0..100 {
+                    thread::sleep(Duration::from_micros(100));
+                    tx.send(i).unwrap();
+                }
+            });
+
+            for i in 0..100 {
+                assert_eq!(rx.recv_timeout(Duration::from_secs(10)), Ok(i));
+            }
+            assert_eq!(rx.recv(), Err(RecvError));
+        });
+    }
+}
@@ -0,0 +1,498 @@
+//! Chase-Lev work-stealing deque.
+//!
+//! The owner of the deque pushes and pops at the bottom end, and other threads steal from the top
+//! end.
+//!
+//! Chase and Lev.  Dynamic Circular Work-Stealing Deque.  SPAA 2005.
+//! <https://dl.acm.org/doi/10.1145/1073970.1073974>
+//!
+//! Lê, Pop, Cohen and Zappa Nardelli.  Correct and Efficient Work-Stealing for Weak Memory Models.
+//! PPoPP 2013.  <https://dl.acm.org/doi/10.1145/2442516.2442524>
+//!
+//! With the `check-loom` feature, `top`, `bottom` and the fences are modeled by loom, and the
+//! races on them are checked with `cargo test --features check-loom --lib deque`. The buffer and
+//! its epoch-based reclamation are not modeled.
+
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{fence, AtomicIsize, Ordering};
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{fence, AtomicIsize, Ordering};
+use std::sync::Arc;
+
+use crossbeam_epoch::{unprotected, Atomic, Guard, Owned};
+use crossbeam_utils::CachePadded;
+
+/// Initial capacity of the buffer.
+const MIN_CAP: usize = 16;
+
+/// A circular buffer of slots.
+///
+/// The buffer does not track which slots are initialized, so dropping it does not drop the values
+/// in it.
+#[derive(Debug)]
+struct Buffer<T> {
+    ptr: *mut MaybeUninit<T>,
+    cap: usize,
+}
+
+impl<T> Buffer<T> {
+    /// Allocates a buffer with the given capacity, which should be a power of two.
+    fn alloc(cap: usize) -> Self {
+        debug_assert!(cap.is_power_of_two());
+        let mut v = Vec::<MaybeUninit<T>>::with_capacity(cap);
+        let ptr = v.as_mut_ptr();
+        mem::forget(v);
+        Self { ptr, cap }
+    }
+
+    /// Returns a pointer to the slot for the given index.
+    fn at(&self, index: isize) -> *mut MaybeUninit<T> {
+        // `cap` is a power of two, so masking computes the index modulo `cap`.
+        unsafe { self.ptr.add(index as usize & (self.cap - 1)) }
+    }
+
+    /// Writes a value to the slot for the given index.
+    ///
+    /// Uses a volatile write because the slot may be concurrently read by a stealer. Such a
+    /// stealer will fail to take the value and forget what it read.
+    unsafe fn write(&self, index: isize, value: T) {
+        ptr::write_volatile(self.at(index), MaybeUninit::new(value))
+    }
+
+    /// Reads the value in the slot for the given index.
+    ///
+    /// The caller becomes the owner of the value only if it wins the race for the slot. Otherwise,
+    /// it should forget the value.
+    unsafe fn read(&self, index: isize) -> MaybeUninit<T> {
+        ptr::read_volatile(self.at(index))
+    }
+}
+
+impl<T> Drop for Buffer<T> {
+    fn drop(&mut self) {
+        // SAFETY: `ptr` and `cap` come from a `Vec` in `alloc()`, and the length 0 makes sure that
+        // the values are not dropped.
+        drop(unsafe { Vec::from_raw_parts(self.ptr, 0, self.cap) });
+    }
+}
+
+#[derive(Debug)]
+struct Inner<T> {
+    /// Index of the oldest value. Incremented by stealers and by the owner popping the last value.
+    top: CachePadded<AtomicIsize>,
+    /// Index one past the newest value. Only written by the owner.
+    bottom: CachePadded<AtomicIsize>,
+    /// The current buffer. Only replaced by the owner, and old buffers are reclaimed by epochs.
+    buffer: CachePadded<Atomic<Buffer<T>>>,
+}
+
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        let top = self.top.load(Ordering::Relaxed);
+        let bottom = self.bottom.load(Ordering::Relaxed);
+
+        // SAFETY: we have `&mut self`, so neither the owner nor stealers can access the deque.
+        let buffer = unsafe { mem::take(&mut *self.buffer).into_owned() };
+        for i in top..bottom {
+            // SAFETY: the values in `top..bottom` are initialized and owned by the deque.
+            drop(unsafe { buffer.read(i).assume_init() });
+        }
+    }
+}
+
+/// The owner side of a work-stealing deque.
+///
+/// Only the owner pushes and pops, so `Worker` is `Send` but not `Sync`.
+#[derive(Debug)]
+pub struct Worker<T> {
+    inner: Arc<Inner<T>>,
+    _marker: PhantomData<*mut ()>,
+}
+
+/// The stealer side of a work-stealing deque, created by [`Worker::stealer`].
+#[derive(Debug)]
+pub struct Stealer<T> {
+    inner: Arc<Inner<T>>,
+}
+
+/// The result of a steal operation.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Steal<T> {
+    /// The deque was observed to be empty.
+    Empty,
+    /// A value was stolen.
+    Success(T),
+    /// Lost the race to the owner or another stealer; the operation can be retried.
+    Retry,
+}
+
+unsafe impl<T: Send> Send for Worker<T> {}
+unsafe impl<T: Send> Send for Stealer<T> {}
+unsafe impl<T: Send> Sync for Stealer<T> {}
+
+impl<T> Default for Worker<T> {
+    fn default() -> Self {
+        let buffer = Owned::new(Buffer::alloc(MIN_CAP));
+        Self {
+            inner: Arc::new(Inner {
+                top: CachePadded::new(AtomicIsize::new(0)),
+                bottom: CachePadded::new(AtomicIsize::new(0)),
+                buffer: CachePadded::new(Atomic::from(buffer)),
+            }),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Worker<T> {
+    /// Creates a new, empty deque.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a stealer for the deque.
+    pub fn stealer(&self) -> Stealer<T> {
+        Stealer {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Returns the current buffer.
+    fn buffer(&self) -> &Buffer<T> {
+        // SAFETY: only the owner replaces the buffer, so the current buffer is not retired while the
+        // owner uses it.
+        unsafe {
+            self.inner
+                .buffer
+                .load(Ordering::Relaxed, unprotected())
+                .deref()
+        }
+    }
+
+    /// Moves the values in `top..bottom` to a new buffer with the given capacity.
+    fn resize(&self, top: isize, bottom: isize, cap: usize, guard: &Guard) {
+        let old = self.inner.buffer.load(Ordering::Relaxed, guard);
+        // SAFETY: `old` is the current buffer, which is never null.
+        let old_ref = unsafe { old.deref() };
+        let new = Buffer::alloc(cap);
+        for i in top..bottom {
+            // SAFETY: the slots are in different buffers, and the values in `top..bottom` are
+            // initialized. Stealers may still read them from `old`, but they take at most one copy
+            // of each by racing on `top`.
+            unsafe { ptr::copy_nonoverlapping(old_ref.at(i), new.at(i), 1) };
+        }
+
+        // Release: stealers that load the new buffer should see the copied values.
+        self.inner.buffer.store(Owned::new(new), Ordering::Release);
+        // SAFETY: `old` is unreachable from the deque now. Dropping a `Buffer` does not drop the
+        // values, which have been moved to the new buffer.
+        unsafe { guard.defer_destroy(old) };
+    }
+
+    /// Pushes a value to the bottom of the deque.
+    pub fn push(&self, value: T, guard: &Guard) {
+        let bottom = self.inner.bottom.load(Ordering::Relaxed);
+        let top = self.inner.top.load(Ordering::Acquire);
+
+        let cap = self.buffer().cap;
+        if bottom - top >= cap as isize {
+            self.resize(top, bottom, 2 * cap, guard);
+        }
+
+        // SAFETY: the slot for `bottom` is not in `top..bottom`, so it's not owned by anyone.
+        unsafe { self.buffer().write(bottom, value) };
+
+        // Release: stealers that see the new `bottom` should see the value.
+        fence(Ordering::Release);
+        self.inner.bottom.store(bottom + 1, Ordering::Relaxed);
+    }
+
+    /// Pops a value from the bottom of the deque.
+    ///
+    /// Returns `None` if the deque is observed to be empty.
+    pub fn pop(&self) -> Option<T> {
+        let bottom = self.inner.bottom.load(Ordering::Relaxed) - 1;
+        self.inner.bottom.store(bottom, Ordering::Relaxed);
+
+        // SeqCst: either the stealers see the decremented `bottom`, or we see their increment of
+        // `top`, so that the last value is not taken twice.
+        fence(Ordering::SeqCst);
+        let top = self.inner.top.load(Ordering::Relaxed);
+
+        if bottom < top {
+            // The deque is empty.
+            self.inner.bottom.store(bottom + 1, Ordering::Relaxed);
+            return None;
+        }
+
+        // SAFETY: `bottom` is in `top..=bottom`, so the slot is initialized.
+        let value = unsafe { self.buffer().read(bottom) };
+
+        if bottom > top {
+            // There are other values, so no stealer can reach `bottom`.
+            // SAFETY: we took the value out of the deque.
+            return Some(unsafe { value.assume_init() });
+        }
+
+        // This is the last value, so race with the stealers on `top`.
+        let won = self
+            .inner
+            .top
+            .compare_exchange(top, top + 1, Ordering::SeqCst, Ordering::Relaxed)
+            .is_ok();
+        self.inner.bottom.store(bottom + 1, Ordering::Relaxed);
+
+        // SAFETY: we took the value out of the deque iff we won the race. Otherwise, it's owned by
+        // the stealer and we forget our copy.
+        won.then(|| unsafe { value.assume_init() })
+    }
+
+    /// Returns `true` if the deque is empty.
+    pub fn is_empty(&self) -> bool {
+        let bottom = self.inner.bottom.load(Ordering::Relaxed);
+        let top = self.inner.top.load(Ordering::Relaxed);
+        bottom <= top
+    }
+}
+
+impl<T> Clone for Stealer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Stealer<T> {
+    /// Steals a value from the top of the deque.
+    pub fn steal(&self, guard: &Guard) -> Steal<T> {
+        let top = self.inner.top.load(Ordering::Acquire);
+
+        // SeqCst: pairs with the fence in `pop()`.
+        fence(Ordering::SeqCst);
+        let bottom = self.inner.bottom.load(Ordering::Acquire);
+        if bottom <= top {
+            return Steal::Empty;
+        }
+
+        // Acquire: to see the values copied to a new buffer.
+        let buffer = self.inner.buffer.load(Ordering::Acquire, guard);
+        // SAFETY: the buffer is never null, and the guard keeps it from being freed. The value is
+        // read before the CAS below, because after the CAS the owner may overwrite the slot.
+        let value = unsafe { buffer.deref().read(top) };
+
+        if self
+            .inner
+            .top
+            .compare_exchange(top, top + 1, Ordering::SeqCst, Ordering::Relaxed)
+            .is_err()
+        {
+            // Someone else took the value, so we forget our copy.
+            return Steal::Retry;
+        }
+
+        // SAFETY: we took the value out of the deque by winning the race on `top`.
+        Steal::Success(unsafe { value.assume_init() })
+    }
+
+    /// Returns `true` if the deque is empty.
+    pub fn is_empty(&self) -> bool {
+        let top = self.inner.top.load(Ordering::Acquire);
+        let bottom = self.inner.bottom.load(Ordering::Acquire);
+        bottom <= top
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod test {
+    use super::*;
+    use core::sync::atomic::AtomicUsize;
+    use crossbeam_epoch::pin;
+    use std::thread::scope;
+
+    #[test]
+    fn push_pop_steal() {
+        let worker = Worker::new();
+        let stealer = worker.stealer();
+        let guard = &pin();
+
+        assert!(worker.is_empty());
+        assert_eq!(worker.pop(), None);
+        assert_eq!(stealer.steal(guard), Steal::Empty);
+
+        // Grow the buffer a few times.
+        for i in 0..100 {
+            worker.push(i, guard);
+        }
+        assert!(!stealer.is_empty());
+        assert_eq!(stealer.steal(guard), Steal::Success(0));
+        assert_eq!(worker.pop(), Some(99));
+        assert_eq!(stealer.steal(guard), Steal::Success(1));
+        assert_eq!(worker.pop(), Some(98));
+        for i in (2..98).rev() {
+            assert_eq!(worker.pop(), Some(i));
+        }
+        assert!(worker.is_empty());
+        assert_eq!(stealer.steal(guard), Steal::Empty);
+    }
+
+    #[test]
+    fn drop_values() {
+        struct Counted<'a>(&'a AtomicUsize);
+        impl Drop for Counted<'_> {
+            fn drop(&mut self) {
+                let _ = self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let dropped = AtomicUsize::new(0);
+        {
+            let worker = Worker::new();
+            let guard = &pin();
+            for _ in 0..50 {
+                worker.push(Counted(&dropped), guard);
+            }
+            drop(worker.pop());
+            drop(worker.stealer().steal(guard));
+        }
+        assert_eq!(dropped.load(Ordering::Relaxed), 50);
+    }
+
+    #[test]
+    fn steal_concurrent() {
+        const COUNT: usize = 100_000;
+        const STEALERS: usize = 3;
+        let worker = Worker::new();
+        let stealer = worker.stealer();
+        let remaining = AtomicUsize::new(COUNT);
+
+        let sums = scope(|scope| {
+            let handles = (0..STEALERS)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut sum = 0;
+                        while remaining.load(Ordering::Relaxed) > 0 {
+                            if let Steal::Success(i) = stealer.steal(&pin()) {
+                                sum += i;
+                                let _ = remaining.fetch_sub(1, Ordering::Relaxed);
+                            }
+                        }
+                        sum
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let mut sum = 0;
+            for i in 0..COUNT {
+                worker.push(i, &pin());
+                if i % 3 == 0 {
+                    if let Some(i) = worker.pop() {
+                        sum += i;
+                        let _ = remaining.fetch_sub(1, Ordering::Relaxed);
+                    }
+                }
+            }
+            while let Some(i) = worker.pop() {
+                sum += i;
+                let _ = remaining.fetch_sub(1, Ordering::Relaxed);
+            }
+
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .chain([sum])
+                .collect::<Vec<_>>()
+        });
+
+        assert_eq!(sums.into_iter().sum::<usize>(), COUNT * (COUNT - 1) / 2);
+    }
+}
+
+#[cfg(all(test, feature = "check-loom"))]
+mod loom_test {
+    use super::*;
+    use crossbeam_epoch::pin;
+    use loom::thread;
+
+    /// Takes all the values with `pop` until the deque is empty.
+    fn pop_all(worker: &Worker<usize>) -> Vec<usize> {
+        let mut values = Vec::new();
+        while let Some(value) = worker.pop() {
+            values.push(value);
+        }
+        values
+    }
+
+    /// Steals until the deque is observed to be empty.
+    fn steal_all(stealer: &Stealer<usize>) -> Vec<usize> {
+        let mut values = Vec::new();
+        loop {
+            match stealer.steal(&pin()) {
+                Steal::Success(value) => values.push(value),
+                Steal::Empty => return values,
+                Steal::Retry => thread::yield_now(),
+            }
+        }
+    }
+
+    // the owner and a stealer race for the last value, and exactly one of them takes it
+    #[test]
+    fn pop_steal_last() {
+        loom::model(|| {
+            let worker = Worker::new();
+            worker.push(0, &pin());
+            let stealer = worker.stealer();
+
+            let handle = thread::spawn(move || steal_all(&stealer));
+            let mut values = pop_all(&worker);
+            values.extend(handle.join().unwrap());
+            assert_eq!(values, [0]);
+        });
+    }
+
+    // a push concurrent with a steal of the last value is taken exactly once
+    #[test]
+    fn push_pop_steal() {
+        loom::model(|| {
+            let worker = Worker::new();
+            worker.push(0, &pin());
+            let stealer = worker.stealer();
+
+            let handle = thread::spawn(move || steal_all(&stealer));
+            worker.push(1, &pin());
+            let mut values = pop_all(&worker);
+            values.extend(handle.join().unwrap());
+            values.sort_unstable();
+            assert_eq!(values, [0, 1]);
+        });
+    }
+
+    // the values are taken exactly once while the buffer grows under a stealer
+    #[test]
+    fn steal_resize() {
+        loom::model(|| {
+            let worker = Worker::new();
+            for i in 0..MIN_CAP {
+                worker.push(i, &pin());
+            }
+            let stealer = worker.stealer();
+
+            let handle = thread::spawn(move || {
+                let guard = &pin();
+                match stealer.steal(guard) {
+                    Steal::Success(value) => vec![value],
+                    _ => vec![],
+                }
+            });
+            // The buffer is full, so the push moves the values to a new buffer.
+            worker.push(MIN_CAP, &pin());
+            let mut values = pop_all(&worker);
+            values.extend(handle.join().unwrap());
+            values.sort_unstable();
+            assert_eq!(values, (0..=MIN_CAP).collect::<Vec<_>>());
+        });
+    }
+}
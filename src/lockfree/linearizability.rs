@@ -0,0 +1,249 @@
+//! Linearizability checker for the tests of the lock-free data structures.
+//!
+//! A concurrent workload is run while recording a history: the call and return times of each
+//! operation and its result. Then the Wing-Gong algorithm searches for a linearization, i.e. an
+//! order of the operations that respects their real-time order and in which the sequential
+//! specification produces the recorded results. As in Lowe's variant, the search memoizes the
+//! visited (linearized operations, state) pairs.
+//!
+//! For maps, linearizability is P-compositional: a history is linearizable iff its sub-history for
+//! each key is. [`check_partitioned`] uses this to check much longer histories.
+//!
+//! Wing and Gong.  Testing and Verifying Concurrent Objects.  JPDC 1993.
+//! Lowe.  Testing for Linearizability.  CCPE 2017.
+//! Horn and Kroening.  Faster Linearizability Checking via P-Compositionality.  FORTE 2015.
+
+use core::fmt::Debug;
+use core::hash::Hash;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::{HashMap, HashSet};
+use std::thread::scope;
+
+use super::rng::Rng;
+
+/// Sequential specification of a data structure.
+pub(crate) trait Spec: Clone + Eq + Hash {
+    /// Operation.
+    type Op: Debug;
+    /// Result of an operation.
+    type Ret: Debug + PartialEq;
+
+    /// Applies `op` to the state and returns its result.
+    fn apply(&mut self, op: &Self::Op) -> Self::Ret;
+}
+
+/// A completed operation in a history.
+#[derive(Debug)]
+pub(crate) struct Event<Op, Ret> {
+    call: usize,
+    ret: usize,
+    op: Op,
+    result: Ret,
+}
+
+/// Runs `ops` randomly generated operations on each of `threads` threads and records the history.
+///
+/// `gen` generates an operation for the given thread, and `exec` executes it on the data structure.
+pub(crate) fn record<Op, Ret, G, E>(
+    threads: usize,
+    ops: usize,
+    seed: u64,
+    gen: G,
+    exec: E,
+) -> Vec<Event<Op, Ret>>
+where
+    Op: Send,
+    Ret: Send,
+    G: Fn(usize, &mut Rng) -> Op + Sync,
+    E: Fn(&Op) -> Ret + Sync,
+{
+    let clock = AtomicUsize::new(0);
+    scope(|scope| {
+        let handles = (0..threads)
+            .map(|t| {
+                let (clock, gen, exec) = (&clock, &gen, &exec);
+                scope.spawn(move || {
+                    let mut rng = Rng::new(seed ^ (t as u64 + 1));
+                    let mut events = Vec::with_capacity(ops);
+                    for _ in 0..ops {
+                        let op = gen(t, &mut rng);
+                        // SeqCst: the clock should respect the real-time order of the operations.
+                        let call = clock.fetch_add(1, Ordering::SeqCst);
+                        let result = exec(&op);
+                        let ret = clock.fetch_add(1, Ordering::SeqCst);
+                        events.push(Event {
+                            call,
+                            ret,
+                            op,
+                            result,
+                        });
+                    }
+                    events
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect()
+    })
+}
+
+/// Returns whether `history` is linearizable with respect to the specification starting at
+/// `init`.
+pub(crate) fn check<S: Spec>(init: S, history: &[Event<S::Op, S::Ret>]) -> bool {
+    let mut events = history.iter().collect::<Vec<_>>();
+    events.sort_by_key(|e| e.call);
+    let mut done = vec![false; events.len()];
+    let mut visited = HashSet::new();
+    search(&init, &events, &mut done, events.len(), &mut visited)
+}
+
+/// Partitions `history` by `key`, and checks each sub-history with the specification starting at
+/// `init`.
+pub(crate) fn check_partitioned<S, K, F>(init: S, history: &[Event<S::Op, S::Ret>], key: F) -> bool
+where
+    S: Spec,
+    K: Eq + Hash,
+    F: Fn(&S::Op) -> K,
+{
+    let mut partitions = HashMap::<K, Vec<&Event<S::Op, S::Ret>>>::new();
+    for e in history {
+        partitions.entry(key(&e.op)).or_default().push(e);
+    }
+    partitions.into_values().all(|mut events| {
+        events.sort_by_key(|e| e.call);
+        let mut done = vec![false; events.len()];
+        let mut visited = HashSet::new();
+        search(&init, &events, &mut done, events.len(), &mut visited)
+    })
+}
+
+/// Tries to linearize the remaining operations in `events`, which are sorted by call time, from
+/// `state`.
+fn search<S: Spec>(
+    state: &S,
+    events: &[&Event<S::Op, S::Ret>],
+    done: &mut [bool],
+    remaining: usize,
+    visited: &mut HashSet<(Vec<bool>, S)>,
+) -> bool {
+    if remaining == 0 {
+        return true;
+    }
+
+    // An operation can be linearized next only if it was called before every other remaining
+    // operation returned.
+    let min_ret = events
+        .iter()
+        .zip(done.iter())
+        .filter(|(_, done)| !**done)
+        .map(|(e, _)| e.ret)
+        .min()
+        .unwrap();
+
+    for i in 0..events.len() {
+        if events[i].call > min_ret {
+            break;
+        }
+        if done[i] {
+            continue;
+        }
+
+        let mut next = state.clone();
+        if next.apply(&events[i].op) != events[i].result {
+            continue;
+        }
+
+        done[i] = true;
+        if visited.insert((done.to_vec(), next.clone()))
+            && search(&next, events, done, remaining - 1, visited)
+        {
+            return true;
+        }
+        done[i] = false;
+    }
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::VecDeque;
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct Fifo(VecDeque<u32>);
+
+    #[derive(Debug)]
+    enum Op {
+        Push(u32),
+        Pop,
+    }
+
+    impl Spec for Fifo {
+        type Op = Op;
+        type Ret = Option<u32>;
+
+        fn apply(&mut self, op: &Op) -> Option<u32> {
+            match *op {
+                Op::Push(v) => {
+                    self.0.push_back(v);
+                    None
+                }
+                Op::Pop => self.0.pop_front(),
+            }
+        }
+    }
+
+    fn event(call: usize, ret: usize, op: Op, result: Option<u32>) -> Event<Op, Option<u32>> {
+        Event {
+            call,
+            ret,
+            op,
+            result,
+        }
+    }
+
+    #[test]
+    fn sequential() {
+        let init = Fifo(VecDeque::new());
+        let ok = [
+            event(0, 1, Op::Push(1), None),
+            event(2, 3, Op::Push(2), None),
+            event(4, 5, Op::Pop, Some(1)),
+        ];
+        assert!(check(init.clone(), &ok));
+
+        let lifo = [
+            event(0, 1, Op::Push(1), None),
+            event(2, 3, Op::Push(2), None),
+            event(4, 5, Op::Pop, Some(2)),
+        ];
+        assert!(!check(init, &lifo));
+    }
+
+    #[test]
+    fn overlapping() {
+        let init = Fifo(VecDeque::new());
+
+        // The pushes overlap, so they can be linearized in either order.
+        let ok = [
+            event(0, 3, Op::Push(1), None),
+            event(1, 2, Op::Push(2), None),
+            event(4, 5, Op::Pop, Some(2)),
+            event(6, 7, Op::Pop, Some(1)),
+        ];
+        assert!(check(init.clone(), &ok));
+
+        // A pop may return nothing after a push completed only if a concurrent pop took the
+        // value.
+        let stale = [
+            event(0, 1, Op::Push(1), None),
+            event(2, 5, Op::Pop, None),
+            event(3, 4, Op::Pop, Some(1)),
+        ];
+        assert!(check(init.clone(), &stale));
+        let stale = [event(0, 1, Op::Push(1), None), event(2, 3, Op::Pop, None)];
+        assert!(!check(init, &stale));
+    }
+}
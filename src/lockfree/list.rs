@@ -1,10 +1,32 @@
 //! Lock-free singly linked list.
 
 use core::cmp::Ordering::{Equal, Greater, Less};
+use core::marker::PhantomData;
 use core::mem;
-use core::sync::atomic::Ordering;
+use core::ops::{Bound, RangeBounds};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 use crossbeam_epoch::{Atomic, Guard, Owned, Shared};
+use crossbeam_utils::CachePadded;
+
+#[cfg(feature = "stats")]
+use super::stats::Stats;
+use super::stats::{Counters, Tracker};
+use crate::reclaim::{Epoch, Reclaimer};
+
+/// Number of fast-path attempts of `kogan_petrank_insert` before it announces itself.
+const FAST_PATH_ATTEMPTS: usize = 16;
+
+/// Number of insertions that can be announced at the same time.
+const ANNOUNCEMENTS: usize = 16;
+
+/// State of an announced insertion that is not completed yet.
+const PENDING: usize = 0;
+
+/// State of an announced insertion that failed because the key is present. Otherwise, the state
+/// of a completed insertion is the address of the inserted node.
+const FAILED: usize = 1;
 
 /// Linked list node.
 #[derive(Debug)]
@@ -13,27 +35,83 @@ pub struct Node<K, V> {
     next: Atomic<Node<K, V>>,
     key: K,
     value: V,
+    /// The announced insertion if the node is a copy inserted by its helper.
+    insertion: Option<Arc<Insertion<K, V>>>,
+    tracker: Tracker,
+}
+
+/// An insertion announced by `kogan_petrank_insert` for the other threads to help.
+///
+/// Each helper inserts its own copy of the entry, and the first copy observed in the list
+/// completes the insertion. The other copies that are inserted by the late helpers are logically
+/// deleted, and marked by the threads that find them.
+#[derive(Debug)]
+struct Insertion<K, V> {
+    key: K,
+    value: V,
+    /// `PENDING`, `FAILED`, or the address of the node that completed the insertion.
+    state: AtomicUsize,
 }
 
 /// Sorted singly linked list.
 ///
 /// Use-after-free will be caused when an unprotected guard is used, as the lifetime of returned
 /// elements are linked to that of the guard in the same way a `Shared<'g,T>` is.
+///
+/// # Iteration
+///
+/// [`List::iter`] and [`List::range`] are weakly consistent. They yield keys in strictly ascending
+/// order and never yield the same key twice. A key that is present and not deleted during the
+/// whole iteration is always yielded. A key that is concurrently inserted or deleted may or may not
+/// be yielded.
+///
+/// # Progress
+///
+/// All operations are lock-free: an individual update may restart indefinitely under contention.
+/// The `kogan_petrank*` operations follow the fast-path-slow-path methodology of Kogan and Petrank.
+/// An insertion that fails `FAST_PATH_ATTEMPTS` times announces itself, and the other
+/// `kogan_petrank*` updates help the announced insertions before their own operations. The
+/// helpers insert their own copies of the entry, and the first copy found in the list completes
+/// the insertion. Hence, an announced insertion completes after a bounded number of attempts if
+/// all the updates of the list are `kogan_petrank*`. [`List::kogan_petrank_lookup`] is wait-free.
+///
+/// Kogan and Petrank. A Methodology for Creating Fast Wait-Free Data Structures. PPoPP 2012.
+///
+/// # Reclamation
+///
+/// The nodes are reclaimed with the memory reclamation scheme `R`. Only the `harris_michael*`
+/// operations are available for every scheme. The others traverse the nodes that may already be
+/// unlinked, so they are sound only with [`Epoch`].
+///
+/// The nodes made with [`Node::new`] outside of the list are not counted in [`List::stats`].
 #[derive(Debug)]
-pub struct List<K, V> {
+pub struct List<K, V, R: Reclaimer = Epoch> {
     head: Atomic<Node<K, V>>,
+    /// Number of announced slow-path insertions.
+    announced: CachePadded<AtomicUsize>,
+    /// The announced slow-path insertions.
+    announcements: [Atomic<Arc<Insertion<K, V>>>; ANNOUNCEMENTS],
+    counters: Counters,
+    _marker: PhantomData<R>,
 }
 
-impl<K, V> Default for List<K, V>
+impl<K, V, R> Default for List<K, V, R>
 where
     K: Ord,
+    R: Reclaimer,
 {
     fn default() -> Self {
-        Self::new()
+        List {
+            head: Atomic::null(),
+            announced: CachePadded::new(AtomicUsize::new(0)),
+            announcements: Default::default(),
+            counters: Counters::default(),
+            _marker: PhantomData,
+        }
     }
 }
 
-impl<K, V> Drop for List<K, V> {
+impl<K, V, R: Reclaimer> Drop for List<K, V, R> {
     fn drop(&mut self) {
         let mut o_curr = mem::take(&mut self.head);
         // SAFETY: since we have `&mut self`, any references from `lookup()` must have finished.
@@ -45,24 +123,50 @@ impl<K, V> Drop for List<K, V> {
 }
 
 /// Linked list cursor.
+///
+/// `curr` is protected by the guard of the reclamation scheme `R`.
 #[derive(Debug, Copy)]
-pub struct Cursor<'g, K, V> {
+pub struct Cursor<'g, K, V, R: Reclaimer = Epoch> {
     prev: &'g Atomic<Node<K, V>>,
+    /// The node of `prev` if the cursor moved to it, null otherwise. It is protected by the guard.
+    prev_node: Shared<'g, Node<K, V>>,
     // Tag of `curr` should always be zero so when `curr` is stored in a `prev`, we don't store a
     // marked pointer and cause cleanup to fail.
     curr: Shared<'g, Node<K, V>>,
+    _marker: PhantomData<R>,
 }
 
 // Manual implementation as deriving `Clone` leads to unnecessary trait bounds.
-impl<'g, K, V> Clone for Cursor<'g, K, V> {
+impl<'g, K, V, R: Reclaimer> Clone for Cursor<'g, K, V, R> {
     fn clone(&self) -> Self {
         Self {
             prev: self.prev,
+            prev_node: self.prev_node,
             curr: self.curr,
+            _marker: PhantomData,
         }
     }
 }
 
+/// Iterator over the entries of a [`List`] in ascending key order.
+///
+/// Logically deleted nodes are skipped. See [`List`] for the consistency guarantees.
+#[derive(Debug)]
+pub struct Iter<'g, K, V> {
+    cursor: Cursor<'g, K, V>,
+    help: bool,
+    guard: &'g Guard,
+}
+
+/// Iterator over the entries of a [`List`] whose keys are in a given range.
+///
+/// Created by [`List::range`].
+#[derive(Debug)]
+pub struct Range<'g, K, V, B> {
+    iter: Iter<'g, K, V>,
+    range: B,
+}
+
 impl<K, V> Node<K, V> {
     /// Creates a new node.
     pub fn new(key: K, value: V) -> Self {
@@ -70,6 +174,8 @@ impl<K, V> Node<K, V> {
             next: Atomic::null(),
             key,
             value,
+            insertion: None,
+            tracker: Tracker::untracked(),
         }
     }
 
@@ -77,17 +183,50 @@ impl<K, V> Node<K, V> {
     pub fn into_value(self) -> V {
         self.value
     }
+
+    /// Returns whether the node is a copy that does not complete its announced insertion.
+    ///
+    /// It should be called only if the node was found in the list unmarked. Such a node is
+    /// logically deleted, and the caller should mark it. Otherwise, if the insertion is pending,
+    /// this node completes it, since it is in the list and no other node has the same key.
+    #[inline]
+    fn is_orphan(&self) -> bool {
+        self.insertion
+            .as_ref()
+            .map_or(false, |insertion| !insertion.complete(self))
+    }
 }
 
-impl<'g, K, V> Cursor<'g, K, V>
+impl<K, V> Insertion<K, V> {
+    /// Completes the insertion with `node` if it is pending, and returns whether it is completed
+    /// with `node`.
+    #[inline]
+    fn complete(&self, node: *const Node<K, V>) -> bool {
+        let node = node as usize;
+        match self
+            .state
+            .compare_exchange(PENDING, node, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => true,
+            Err(state) => state == node,
+        }
+    }
+}
+
+impl<'g, K, V, R> Cursor<'g, K, V, R>
 where
     K: Ord,
+    R: Reclaimer,
 {
     /// Creates a cursor.
+    ///
+    /// `curr` should be protected by the guard of `R`.
     pub fn new(prev: &'g Atomic<Node<K, V>>, curr: Shared<'g, Node<K, V>>) -> Self {
         Self {
             prev,
+            prev_node: Shared::null(),
             curr: curr.with_tag(0),
+            _marker: PhantomData,
         }
     }
 
@@ -96,121 +235,127 @@ where
         self.curr
     }
 
-    /// Clean up a chain of logically removed nodes in each traversal.
+    /// Clean up a single logically removed node in each traversal.
     #[inline]
-    pub fn find_harris(&mut self, key: &K, guard: &'g Guard) -> Result<bool, ()> {
-        // Finding phase
-        // - cursor.curr: first unmarked node w/ key >= search key (4)
-        // - cursor.prev: the ref of .next in previous unmarked node (1 -> 2)
-        // 1 -> 2 -x-> 3 -x-> 4 -> 5 -> ∅  (search key: 4)
-        let mut prev_next = self.curr;
-        let found = loop {
-            let Some(curr_node) = (unsafe { self.curr.as_ref() }) else {
-                break false;
-            };
-            let next = curr_node.next.load(Ordering::Acquire, guard);
-
-            // - finding stage is done if cursor.curr advancement stops
-            // - advance cursor.curr if (.next is marked) || (cursor.curr < key)
-            // - stop cursor.curr if (not marked) && (cursor.curr >= key)
-            // - advance cursor.prev if not marked
-
-            if next.tag() != 0 {
-                // We add a 0 tag here so that `self.curr`s tag is always 0.
-                self.curr = next.with_tag(0);
-                continue;
-            }
-
-            match curr_node.key.cmp(key) {
-                Less => {
-                    self.curr = next;
-                    self.prev = &curr_node.next;
-                    prev_next = next;
-                }
-                Equal => break true,
-                Greater => break false,
-            }
-        };
-
-        // If prev and curr WERE adjacent, no need to clean up
-        if prev_next == self.curr {
-            return Ok(found);
-        }
-
-        // cleanup marked nodes between prev and curr
-        self.prev
-            .compare_exchange(
-                prev_next,
-                self.curr,
-                Ordering::Release,
-                Ordering::Relaxed,
-                guard,
-            )
-            .map_err(|_| ())?;
+    pub fn find_harris_michael(&mut self, key: &K, guard: &'g R::Guard) -> Result<bool, ()> {
+        self.find_harris_michael_by(|k| k.cmp(key), guard)
+    }
 
-        // defer_destroy from cursor.prev.load() to cursor.curr (exclusive)
-        let mut node = prev_next;
-        while node.with_tag(0) != self.curr {
-            // SAFETY: All nodes in the unlinked chain are not null.
-            let next = unsafe { node.deref() }.next.load(Ordering::Relaxed, guard);
-            // SAFETY: we unlinked the chain with above CAS.
-            unsafe { guard.defer_destroy(node) };
-            node = next;
-        }
+    /// Same as `find_harris_michael`, but compares the keys of the nodes with the target using
+    /// `cmp`, which should be monotone along the list.
+    ///
+    /// It allows searching among the nodes with equivalent keys: `cmp` may return `Less` for the
+    /// nodes that are equivalent to the target but not the target itself.
+    #[inline]
+    pub fn find_harris_michael_by<F>(&mut self, cmp: F, guard: &'g R::Guard) -> Result<bool, ()>
+    where
+        F: FnMut(&K) -> core::cmp::Ordering,
+    {
+        // SAFETY: the nodes are not released.
+        unsafe { self.find_harris_michael_inner(cmp, false, guard) }
+    }
 
-        Ok(found)
+    /// Same as `find_harris_michael`, but ends the protection of the nodes that the cursor passes
+    /// by, so that the number of protected nodes does not grow with the length of the traversal.
+    ///
+    /// This is private because the references obtained from the cursor before the call are not
+    /// protected afterwards. The list's operations never hold them across a traversal.
+    #[inline]
+    fn find_harris_michael_releasing(&mut self, key: &K, guard: &'g R::Guard) -> Result<bool, ()> {
+        // SAFETY: see above.
+        unsafe { self.find_harris_michael_inner(|k| k.cmp(key), true, guard) }
     }
 
-    /// Clean up a single logically removed node in each traversal.
+    /// Implementation of the `find_harris_michael*` methods. If `release` is true, the nodes that
+    /// the cursor no longer needs are released.
+    ///
+    /// # Safety
+    ///
+    /// If `release` is true, the references obtained from the cursor before the call must not be
+    /// used afterwards.
     #[inline]
-    pub fn find_harris_michael(&mut self, key: &K, guard: &'g Guard) -> Result<bool, ()> {
+    unsafe fn find_harris_michael_inner<F>(
+        &mut self,
+        mut cmp: F,
+        release: bool,
+        guard: &'g R::Guard,
+    ) -> Result<bool, ()>
+    where
+        F: FnMut(&K) -> core::cmp::Ordering,
+    {
+        let release_if = |ptr| {
+            if release {
+                R::release(ptr, guard);
+            }
+        };
+        let atomic_guard = R::atomic_guard(guard);
         loop {
             debug_assert_eq!(self.curr.tag(), 0);
 
             let Some(curr_node) = (unsafe { self.curr.as_ref() }) else {
                 return Ok(false);
             };
-            let mut next = curr_node.next.load(Ordering::Acquire, guard);
+            let mut next = R::protect(&curr_node.next, Ordering::Acquire, guard);
+
+            // `next` is protected only if `curr` is not retired, i.e. if `prev` still points to
+            // `curr`. This is Michael's validation for hazard pointers.
+            if self.prev.load(Ordering::Acquire, atomic_guard) != self.curr {
+                release_if(next);
+                return Err(());
+            }
+
+            if next.tag() == 0 && curr_node.is_orphan() {
+                let _ = curr_node.next.fetch_or(1, Ordering::AcqRel, atomic_guard);
+                release_if(next);
+                continue;
+            }
 
             if next.tag() != 0 {
                 next = next.with_tag(0);
                 self.prev
-                    .compare_exchange(self.curr, next, Ordering::Release, Ordering::Relaxed, guard)
-                    .map_err(|_| ())?;
-                unsafe { guard.defer_destroy(self.curr) };
+                    .compare_exchange(
+                        self.curr,
+                        next,
+                        Ordering::Release,
+                        Ordering::Relaxed,
+                        atomic_guard,
+                    )
+                    .map_err(|_| release_if(next))?;
+                curr_node.tracker.retire();
+                R::retire(self.curr, guard);
+                release_if(self.curr);
                 self.curr = next;
                 continue;
             }
 
-            match curr_node.key.cmp(key) {
+            match cmp(&curr_node.key) {
                 Less => {
+                    release_if(self.prev_node);
                     self.prev = &curr_node.next;
+                    self.prev_node = self.curr;
                     self.curr = next;
                 }
-                Equal => return Ok(true),
-                Greater => return Ok(false),
+                Equal => {
+                    release_if(next);
+                    return Ok(true);
+                }
+                Greater => {
+                    release_if(next);
+                    return Ok(false);
+                }
             }
         }
     }
 
-    /// Gotta go fast. Doesn't fail.
+    /// Ends the protection of the nodes of the cursor. See [`Reclaimer::release`].
+    ///
+    /// # Safety
+    ///
+    /// The references obtained from the cursor must not be used afterwards.
     #[inline]
-    pub fn find_harris_herlihy_shavit(&mut self, key: &K, guard: &'g Guard) -> Result<bool, ()> {
-        Ok(loop {
-            let Some(curr_node) = (unsafe { self.curr.as_ref() }) else {
-                break false;
-            };
-            match curr_node.key.cmp(key) {
-                Less => {
-                    self.curr = curr_node.next.load(Ordering::Acquire, guard);
-                    // NOTE: unnecessary (this function is expected to be used only for `lookup`)
-                    self.prev = &curr_node.next;
-                    continue;
-                }
-                Equal => break curr_node.next.load(Ordering::Relaxed, guard).tag() == 0,
-                Greater => break false,
-            }
-        })
+    pub unsafe fn release(self, guard: &'g R::Guard) {
+        R::release(self.prev_node, guard);
+        R::release(self.curr, guard);
     }
 
     /// Lookups the value.
@@ -224,132 +369,670 @@ where
     pub fn insert(
         &mut self,
         node: Owned<Node<K, V>>,
-        guard: &'g Guard,
+        guard: &'g R::Guard,
     ) -> Result<(), Owned<Node<K, V>>> {
         node.next.store(self.curr, Ordering::Relaxed);
+        // `node` is protected since the cursor moves to it.
+        let node = R::protect_owned(node, guard);
         match self.prev.compare_exchange(
             self.curr,
             node,
             Ordering::Release,
             Ordering::Relaxed,
-            guard,
+            R::atomic_guard(guard),
         ) {
             Ok(node) => {
                 self.curr = node;
                 Ok(())
             }
-            Err(e) => Err(e.new),
+            // SAFETY: the CAS failed, so `node` is not shared.
+            Err(e) => unsafe {
+                R::release(e.new, guard);
+                Err(e.new.into_owned())
+            },
+        }
+    }
+
+    /// Replaces the current node with `node`, which should have the same key.
+    ///
+    /// On success, returns the value of the replaced node and moves the cursor to `node`.
+    #[inline]
+    pub fn replace(
+        &mut self,
+        node: Owned<Node<K, V>>,
+        guard: &'g R::Guard,
+    ) -> Result<&'g V, Owned<Node<K, V>>> {
+        let atomic_guard = R::atomic_guard(guard);
+
+        // SAFETY: curr was found, hence cannot be null.
+        let curr_node = unsafe { self.curr.deref() };
+
+        let next = curr_node.next.load(Ordering::Acquire, atomic_guard);
+        if next.tag() != 0 {
+            return Err(node);
+        }
+
+        // Marks `curr` while redirecting it to `node` in a single CAS, so that `node` takes the
+        // place of `curr` atomically: traversals skip the marked `curr` and arrive at `node`.
+        node.next.store(next, Ordering::Relaxed);
+        let node = R::protect_owned(node, guard);
+        let node = match curr_node.next.compare_exchange(
+            next,
+            node.with_tag(1),
+            Ordering::AcqRel,
+            Ordering::Relaxed,
+            atomic_guard,
+        ) {
+            Ok(node) => node.with_tag(0),
+            // SAFETY: the CAS failed, so `node` is not shared.
+            Err(e) => unsafe {
+                let node = e.new.with_tag(0);
+                R::release(node, guard);
+                return Err(node.into_owned());
+            },
+        };
+
+        if self
+            .prev
+            .compare_exchange(
+                self.curr,
+                node,
+                Ordering::Release,
+                Ordering::Relaxed,
+                atomic_guard,
+            )
+            .is_ok()
+        {
+            // SAFETY: we are unlinker of curr. As the lifetime of the guard extends to the return
+            // value of the function, later access of curr_node is ok.
+            curr_node.tracker.retire();
+            unsafe { R::retire(self.curr, guard) };
         }
+
+        self.curr = node;
+        Ok(&curr_node.value)
     }
 
     /// Deletes the current node.
     #[inline]
-    pub fn delete(self, guard: &'g Guard) -> Result<&'g V, ()> {
+    pub fn delete(self, guard: &'g R::Guard) -> Result<&'g V, ()> {
+        let atomic_guard = R::atomic_guard(guard);
+
         // SAFETY: curr was found, hence cannot be null.
         let curr_node = unsafe { self.curr.deref() };
 
         // Release: to release current view of the deleting thread on this mark.
         // Acquire: to ensure that if the latter CAS succeeds, then the thread that reads `next` through `prev` will be safe.
-        let next = curr_node.next.fetch_or(1, Ordering::AcqRel, guard);
+        let next = curr_node.next.fetch_or(1, Ordering::AcqRel, atomic_guard);
         if next.tag() == 1 {
             return Err(());
         }
 
         if self
             .prev
-            .compare_exchange(self.curr, next, Ordering::Release, Ordering::Relaxed, guard)
+            .compare_exchange(
+                self.curr,
+                next,
+                Ordering::Release,
+                Ordering::Relaxed,
+                atomic_guard,
+            )
             .is_ok()
         {
             // SAFETY: we are unlinker of curr. As the lifetime of the guard extends to the return
             // value of the function, later access of curr_node is ok.
-            unsafe { guard.defer_destroy(self.curr) };
+            curr_node.tracker.retire();
+            unsafe { R::retire(self.curr, guard) };
         }
 
         Ok(&curr_node.value)
     }
 }
 
-impl<K, V> List<K, V>
+impl<'g, K, V> Cursor<'g, K, V>
 where
     K: Ord,
 {
-    /// Creates a new list.
-    pub fn new() -> Self {
-        List {
-            head: Atomic::null(),
-        }
-    }
-
-    /// Creates the head cursor.
+    /// Clean up a chain of logically removed nodes in each traversal.
     #[inline]
-    pub fn head<'g>(&'g self, guard: &'g Guard) -> Cursor<'g, K, V> {
-        Cursor::new(&self.head, self.head.load(Ordering::Acquire, guard))
-    }
+    pub fn find_harris(&mut self, key: &K, guard: &'g Guard) -> Result<bool, ()> {
+        // Finding phase
+        // - cursor.curr: first unmarked node w/ key >= search key (4)
+        // - cursor.prev: the ref of .next in previous unmarked node (1 -> 2)
+        // 1 -> 2 -x-> 3 -x-> 4 -> 5 -> ∅  (search key: 4)
+        let mut prev_next = self.curr;
+        let found = loop {
+            let Some(curr_node) = (unsafe { self.curr.as_ref() }) else {
+                break false;
+            };
+            let next = curr_node.next.load(Ordering::Acquire, guard);
 
-    /// Finds a key using the given find strategy.
-    #[inline]
-    fn find<'g, F>(&'g self, key: &K, find: &F, guard: &'g Guard) -> (bool, Cursor<'g, K, V>)
-    where
-        F: Fn(&mut Cursor<'g, K, V>, &K, &'g Guard) -> Result<bool, ()>,
-    {
-        loop {
-            let mut cursor = self.head(guard);
-            if let Ok(r) = find(&mut cursor, key, guard) {
-                return (r, cursor);
+            if next.tag() == 0 && curr_node.is_orphan() {
+                let _ = curr_node.next.fetch_or(1, Ordering::AcqRel, guard);
+                continue;
             }
-        }
-    }
 
-    #[inline]
-    fn lookup<'g, F>(&'g self, key: &K, find: F, guard: &'g Guard) -> Option<&'g V>
-    where
-        F: Fn(&mut Cursor<'g, K, V>, &K, &'g Guard) -> Result<bool, ()>,
-    {
-        let (found, cursor) = self.find(key, &find, guard);
-        if found {
-            cursor.lookup()
-        } else {
-            None
-        }
-    }
+            // - finding stage is done if cursor.curr advancement stops
+            // - advance cursor.curr if (.next is marked) || (cursor.curr < key)
+            // - stop cursor.curr if (not marked) && (cursor.curr >= key)
+            // - advance cursor.prev if not marked
 
-    #[inline]
-    fn insert<'g, F>(&'g self, key: K, value: V, find: F, guard: &'g Guard) -> bool
-    where
-        F: Fn(&mut Cursor<'g, K, V>, &K, &'g Guard) -> Result<bool, ()>,
-    {
-        let mut node = Owned::new(Node::new(key, value));
-        loop {
-            let (found, mut cursor) = self.find(&node.key, &find, guard);
-            if found {
-                return false;
+            if next.tag() != 0 {
+                // We add a 0 tag here so that `self.curr`s tag is always 0.
+                self.curr = next.with_tag(0);
+                continue;
             }
 
-            match cursor.insert(node, guard) {
-                Err(n) => node = n,
-                Ok(()) => return true,
+            match curr_node.key.cmp(key) {
+                Less => {
+                    self.curr = next;
+                    self.prev = &curr_node.next;
+                    prev_next = next;
+                }
+                Equal => break true,
+                Greater => break false,
             }
-        }
-    }
+        };
 
-    #[inline]
-    fn delete<'g, F>(&'g self, key: &K, find: F, guard: &'g Guard) -> Option<&'g V>
-    where
-        F: Fn(&mut Cursor<'g, K, V>, &K, &'g Guard) -> Result<bool, ()>,
-    {
-        loop {
-            let (found, cursor) = self.find(key, &find, guard);
-            if !found {
-                return None;
-            }
+        // If prev and curr WERE adjacent, no need to clean up
+        if prev_next == self.curr {
+            return Ok(found);
+        }
 
+        // cleanup marked nodes between prev and curr
+        self.prev
+            .compare_exchange(
+                prev_next,
+                self.curr,
+                Ordering::Release,
+                Ordering::Relaxed,
+                guard,
+            )
+            .map_err(|_| ())?;
+
+        // defer_destroy from cursor.prev.load() to cursor.curr (exclusive)
+        let mut node = prev_next;
+        while node.with_tag(0) != self.curr {
+            // SAFETY: All nodes in the unlinked chain are not null.
+            let node_ref = unsafe { node.deref() };
+            let next = node_ref.next.load(Ordering::Relaxed, guard);
+            // SAFETY: we unlinked the chain with above CAS.
+            node_ref.tracker.retire();
+            unsafe { guard.defer_destroy(node) };
+            node = next;
+        }
+
+        Ok(found)
+    }
+
+    /// Gotta go fast. Doesn't fail.
+    #[inline]
+    pub fn find_harris_herlihy_shavit(&mut self, key: &K, guard: &'g Guard) -> Result<bool, ()> {
+        Ok(loop {
+            let Some(curr_node) = (unsafe { self.curr.as_ref() }) else {
+                break false;
+            };
+            match curr_node.key.cmp(key) {
+                Less => {
+                    self.curr = curr_node.next.load(Ordering::Acquire, guard);
+                    // NOTE: unnecessary (this function is expected to be used only for `lookup`)
+                    self.prev = &curr_node.next;
+                    continue;
+                }
+                Equal => {
+                    let next = curr_node.next.load(Ordering::Acquire, guard);
+                    if next.tag() == 0 {
+                        if !curr_node.is_orphan() {
+                            break true;
+                        }
+                        let _ = curr_node.next.fetch_or(1, Ordering::AcqRel, guard);
+                    }
+                    // A replaced node is marked and followed by its replacement with the same key.
+                    // An orphan is followed by the node after it.
+                    self.curr = next.with_tag(0);
+                }
+                Greater => break false,
+            }
+        })
+    }
+}
+
+impl<'g, K, V> Iter<'g, K, V> {
+    /// Makes the iterator help unlink the logically deleted nodes it passes by.
+    ///
+    /// Unlinking is best-effort: if it fails because of a concurrent update, the node is just
+    /// skipped and left to other threads.
+    pub fn help(mut self) -> Self {
+        self.help = true;
+        self
+    }
+}
+
+impl<'g, K, V> Iterator for Iter<'g, K, V> {
+    type Item = (&'g K, &'g V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let curr_node = unsafe { self.cursor.curr.as_ref() }?;
+            let next = curr_node.next.load(Ordering::Acquire, self.guard);
+
+            if next.tag() == 0 && curr_node.is_orphan() {
+                let _ = curr_node.next.fetch_or(1, Ordering::AcqRel, self.guard);
+                continue;
+            }
+
+            if next.tag() != 0 {
+                let next = next.with_tag(0);
+                // If the CAS fails, `prev` is not the predecessor of `curr` anymore. Then the next
+                // unlinking attempts with this `prev` will fail as well, which is harmless.
+                if self.help
+                    && self
+                        .cursor
+                        .prev
+                        .compare_exchange(
+                            self.cursor.curr,
+                            next,
+                            Ordering::Release,
+                            Ordering::Relaxed,
+                            self.guard,
+                        )
+                        .is_ok()
+                {
+                    // SAFETY: we unlinked `curr` with the above CAS.
+                    curr_node.tracker.retire();
+                    unsafe { self.guard.defer_destroy(self.cursor.curr) };
+                }
+                self.cursor.curr = next;
+                continue;
+            }
+
+            self.cursor.prev = &curr_node.next;
+            self.cursor.curr = next;
+            return Some((&curr_node.key, &curr_node.value));
+        }
+    }
+}
+
+impl<'g, K, V, B> Range<'g, K, V, B> {
+    /// Makes the iterator help unlink the logically deleted nodes it passes by.
+    ///
+    /// See [`Iter::help`].
+    pub fn help(mut self) -> Self {
+        self.iter.help = true;
+        self
+    }
+}
+
+impl<'g, K, V, B> Iterator for Range<'g, K, V, B>
+where
+    K: Ord,
+    B: RangeBounds<K>,
+{
+    type Item = (&'g K, &'g V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (key, value) = self.iter.next()?;
+
+            let past_end = match self.range.end_bound() {
+                Bound::Included(end) => key > end,
+                Bound::Excluded(end) => key >= end,
+                Bound::Unbounded => false,
+            };
+            if past_end {
+                // Keys are sorted, so the rest of the list is out of range.
+                self.iter.cursor.curr = Shared::null();
+                return None;
+            }
+
+            if self.range.contains(key) {
+                return Some((key, value));
+            }
+        }
+    }
+}
+
+impl<K, V, R> List<K, V, R>
+where
+    K: Ord,
+    R: Reclaimer,
+{
+    /// Allocates a node counted in the statistics of the list.
+    #[inline]
+    fn node(&self, key: K, value: V) -> Owned<Node<K, V>> {
+        Owned::new(Node {
+            tracker: self.counters.track(),
+            ..Node::new(key, value)
+        })
+    }
+
+    /// Returns the memory reclamation statistics of the list.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.counters.stats()
+    }
+
+    /// Creates the head cursor.
+    #[inline]
+    pub fn head<'g>(&'g self, guard: &'g R::Guard) -> Cursor<'g, K, V, R> {
+        Cursor::new(&self.head, R::protect(&self.head, Ordering::Acquire, guard))
+    }
+
+    /// Finds a key using the given find strategy.
+    #[inline]
+    fn find<'g, F>(&'g self, key: &K, find: &F, guard: &'g R::Guard) -> (bool, Cursor<'g, K, V, R>)
+    where
+        F: Fn(&mut Cursor<'g, K, V, R>, &K, &'g R::Guard) -> Result<bool, ()>,
+    {
+        loop {
+            let mut cursor = self.head(guard);
+            if let Ok(r) = find(&mut cursor, key, guard) {
+                return (r, cursor);
+            }
+            // SAFETY: the cursor is discarded.
+            unsafe { cursor.release(guard) };
+        }
+    }
+
+    #[inline]
+    fn lookup<'g, F>(&'g self, key: &K, find: F, guard: &'g R::Guard) -> Option<&'g V>
+    where
+        F: Fn(&mut Cursor<'g, K, V, R>, &K, &'g R::Guard) -> Result<bool, ()>,
+    {
+        let (found, cursor) = self.find(key, &find, guard);
+        if found {
+            // SAFETY: only `curr` is accessed afterwards, and it stays protected.
+            unsafe { R::release(cursor.prev_node, guard) };
+            cursor.lookup()
+        } else {
+            // SAFETY: the cursor is discarded.
+            unsafe { cursor.release(guard) };
+            None
+        }
+    }
+
+    #[inline]
+    fn insert<'g, F>(&'g self, key: K, value: V, find: F, guard: &'g R::Guard) -> bool
+    where
+        F: Fn(&mut Cursor<'g, K, V, R>, &K, &'g R::Guard) -> Result<bool, ()>,
+    {
+        let mut node = self.node(key, value);
+        loop {
+            let (found, mut cursor) = self.find(&node.key, &find, guard);
+            let next = cursor.curr;
+            let result = if found {
+                Ok(false)
+            } else {
+                cursor.insert(node, guard).map(|()| true)
+            };
+
+            // SAFETY: the cursor is discarded. If `node` is inserted, the cursor moved to it, so
+            // its next node is released separately.
+            unsafe {
+                if matches!(result, Ok(true)) {
+                    R::release(next, guard);
+                }
+                cursor.release(guard);
+            }
+            match result {
+                Err(n) => node = n,
+                Ok(inserted) => return inserted,
+            }
+        }
+    }
+
+    #[inline]
+    fn delete<'g, F>(&'g self, key: &K, find: F, guard: &'g R::Guard) -> Option<&'g V>
+    where
+        F: Fn(&mut Cursor<'g, K, V, R>, &K, &'g R::Guard) -> Result<bool, ()>,
+    {
+        loop {
+            let (found, cursor) = self.find(key, &find, guard);
+            if !found {
+                // SAFETY: the cursor is discarded.
+                unsafe { cursor.release(guard) };
+                return None;
+            }
+
+            // SAFETY: only `curr` is accessed afterwards, and it stays protected on success.
+            unsafe { R::release(cursor.prev_node, guard) };
+            let curr = cursor.curr;
             match cursor.delete(guard) {
-                Err(()) => continue,
+                Err(()) => unsafe { R::release(curr, guard) },
                 Ok(value) => return Some(value),
             }
         }
     }
 
+    #[inline]
+    fn insert_or_replace<'g, F>(
+        &'g self,
+        key: K,
+        value: V,
+        find: F,
+        guard: &'g R::Guard,
+    ) -> Option<&'g V>
+    where
+        F: Fn(&mut Cursor<'g, K, V, R>, &K, &'g R::Guard) -> Result<bool, ()>,
+    {
+        let mut node = self.node(key, value);
+        loop {
+            let (found, mut cursor) = self.find(&node.key, &find, guard);
+            let old = cursor.curr;
+            let result = if found {
+                cursor.replace(node, guard).map(Some)
+            } else {
+                cursor.insert(node, guard).map(|_| None)
+            };
+
+            // SAFETY: the cursor is discarded. If `node` is inserted, the cursor moved to it, so
+            // `old` is released separately unless it is replaced and its value is returned.
+            unsafe {
+                if matches!(result, Ok(None)) {
+                    R::release(old, guard);
+                }
+                cursor.release(guard);
+            }
+            match result {
+                Err(n) => node = n,
+                Ok(value) => return value,
+            }
+        }
+    }
+
+    #[inline]
+    fn compute<'g, F, G>(&'g self, key: K, mut f: G, find: F, guard: &'g R::Guard) -> Option<&'g V>
+    where
+        F: Fn(&mut Cursor<'g, K, V, R>, &K, &'g R::Guard) -> Result<bool, ()>,
+        G: FnMut(Option<&V>) -> Option<V>,
+    {
+        let mut key = key;
+        loop {
+            let (found, mut cursor) = self.find(&key, &find, guard);
+            let (prev_node, old) = (cursor.prev_node, cursor.curr);
+            // SAFETY: called after `f` is done with `curr`. Only the new node, if any, is accessed
+            // afterwards.
+            let release = || unsafe {
+                R::release(prev_node, guard);
+                R::release(old, guard);
+            };
+            let curr = if found { cursor.lookup() } else { None };
+
+            match (f(curr), found) {
+                (None, false) => {
+                    release();
+                    return None;
+                }
+                (None, true) => {
+                    let deleted = cursor.delete(guard).is_ok();
+                    release();
+                    if deleted {
+                        return None;
+                    }
+                }
+                (Some(value), false) => {
+                    let result = cursor.insert(self.node(key, value), guard);
+                    release();
+                    match result {
+                        Ok(()) => return cursor.lookup(),
+                        Err(n) => key = n.into_box().key,
+                    }
+                }
+                (Some(value), true) => {
+                    let result = cursor.replace(self.node(key, value), guard);
+                    release();
+                    match result {
+                        Ok(_) => return cursor.lookup(),
+                        Err(n) => key = n.into_box().key,
+                    }
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn compare_and_swap<'g, F>(
+        &'g self,
+        key: K,
+        expected: &V,
+        new: V,
+        find: F,
+        guard: &'g R::Guard,
+    ) -> bool
+    where
+        V: PartialEq,
+        F: Fn(&mut Cursor<'g, K, V, R>, &K, &'g R::Guard) -> Result<bool, ()>,
+    {
+        let mut node = self.node(key, new);
+        loop {
+            let (found, mut cursor) = self.find(&node.key, &find, guard);
+            let old = cursor.curr;
+            let result = if !found || cursor.lookup() != Some(expected) {
+                Ok(false)
+            } else {
+                // The value of a node never changes, and `replace()` fails if `curr` was
+                // concurrently deleted or replaced. Hence, success means that the value was
+                // `expected` at the CAS.
+                cursor.replace(node, guard).map(|_| true)
+            };
+
+            // SAFETY: the cursor is discarded. If `node` replaced `old`, the cursor moved to it,
+            // so `old` is released separately.
+            unsafe {
+                if matches!(result, Ok(true)) {
+                    R::release(old, guard);
+                }
+                cursor.release(guard);
+            }
+            match result {
+                Err(n) => node = n,
+                Ok(swapped) => return swapped,
+            }
+        }
+    }
+
+    /// Omitted
+    pub fn harris_michael_lookup<'g>(&'g self, key: &K, guard: &'g R::Guard) -> Option<&'g V> {
+        self.lookup(key, Cursor::find_harris_michael_releasing, guard)
+    }
+
+    /// Omitted
+    pub fn harris_michael_insert(&self, key: K, value: V, guard: &R::Guard) -> bool {
+        self.insert(key, value, Cursor::find_harris_michael_releasing, guard)
+    }
+
+    /// Omitted
+    pub fn harris_michael_delete<'g>(&'g self, key: &K, guard: &'g R::Guard) -> Option<&'g V> {
+        self.delete(key, Cursor::find_harris_michael_releasing, guard)
+    }
+
+    /// Omitted
+    pub fn harris_michael_insert_or_replace<'g>(
+        &'g self,
+        key: K,
+        value: V,
+        guard: &'g R::Guard,
+    ) -> Option<&'g V> {
+        self.insert_or_replace(key, value, Cursor::find_harris_michael_releasing, guard)
+    }
+
+    /// Omitted
+    pub fn harris_michael_compute<'g, F>(
+        &'g self,
+        key: K,
+        f: F,
+        guard: &'g R::Guard,
+    ) -> Option<&'g V>
+    where
+        F: FnMut(Option<&V>) -> Option<V>,
+    {
+        self.compute(key, f, Cursor::find_harris_michael_releasing, guard)
+    }
+
+    /// Omitted
+    pub fn harris_michael_compare_and_swap<'g>(
+        &'g self,
+        key: K,
+        expected: &V,
+        new: V,
+        guard: &'g R::Guard,
+    ) -> bool
+    where
+        V: PartialEq,
+    {
+        self.compare_and_swap(
+            key,
+            expected,
+            new,
+            Cursor::find_harris_michael_releasing,
+            guard,
+        )
+    }
+}
+
+impl<K, V> List<K, V>
+where
+    K: Ord,
+{
+    /// Creates a new list.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns an iterator over the entries in ascending key order.
+    #[inline]
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, K, V> {
+        Iter {
+            cursor: self.head(guard),
+            help: false,
+            guard,
+        }
+    }
+
+    /// Returns an iterator over the entries whose keys are in `range`, in ascending key order.
+    #[inline]
+    pub fn range<'g, B>(&'g self, range: B, guard: &'g Guard) -> Range<'g, K, V, B>
+    where
+        B: RangeBounds<K>,
+    {
+        // Seek to the first key not less than the start bound. An excluded start key is skipped
+        // by `Range::next`.
+        let cursor = match range.start_bound() {
+            Bound::Included(start) | Bound::Excluded(start) => {
+                self.find(start, &Cursor::find_harris_michael, guard).1
+            }
+            Bound::Unbounded => self.head(guard),
+        };
+        Range {
+            iter: Iter {
+                cursor,
+                help: false,
+                guard,
+            },
+            range,
+        }
+    }
+
     /// Omitted
     pub fn harris_lookup<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
         self.lookup(key, Cursor::find_harris, guard)
@@ -365,19 +1048,42 @@ where
         self.delete(key, Cursor::find_harris, guard)
     }
 
-    /// Omitted
-    pub fn harris_michael_lookup<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
-        self.lookup(key, Cursor::find_harris_michael, guard)
+    /// Inserts `value` for `key`, replacing the existing value if any.
+    ///
+    /// Returns the replaced value.
+    pub fn harris_insert_or_replace<'g>(
+        &'g self,
+        key: K,
+        value: V,
+        guard: &'g Guard,
+    ) -> Option<&'g V> {
+        self.insert_or_replace(key, value, Cursor::find_harris, guard)
     }
 
-    /// Omitted
-    pub fn harris_michael_insert(&self, key: K, value: V, guard: &Guard) -> bool {
-        self.insert(key, value, Cursor::find_harris_michael, guard)
+    /// Updates the value for `key` to `f(current value)`, where `None` means absence.
+    ///
+    /// `f` may be called multiple times under contention. Returns the new value.
+    pub fn harris_compute<'g, F>(&'g self, key: K, f: F, guard: &'g Guard) -> Option<&'g V>
+    where
+        F: FnMut(Option<&V>) -> Option<V>,
+    {
+        self.compute(key, f, Cursor::find_harris, guard)
     }
 
-    /// Omitted
-    pub fn harris_michael_delete<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
-        self.delete(key, Cursor::find_harris_michael, guard)
+    /// Replaces the value for `key` with `new` if the current value is `expected`.
+    ///
+    /// Returns whether the value was replaced.
+    pub fn harris_compare_and_swap<'g>(
+        &'g self,
+        key: K,
+        expected: &V,
+        new: V,
+        guard: &'g Guard,
+    ) -> bool
+    where
+        V: PartialEq,
+    {
+        self.compare_and_swap(key, expected, new, Cursor::find_harris, guard)
     }
 
     /// Omitted
@@ -394,4 +1100,608 @@ where
     pub fn harris_herlihy_shavit_delete<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
         self.delete(key, Cursor::find_harris_michael, guard)
     }
+
+    /// Omitted
+    pub fn harris_herlihy_shavit_insert_or_replace<'g>(
+        &'g self,
+        key: K,
+        value: V,
+        guard: &'g Guard,
+    ) -> Option<&'g V> {
+        self.insert_or_replace(key, value, Cursor::find_harris_michael, guard)
+    }
+
+    /// Omitted
+    pub fn harris_herlihy_shavit_compute<'g, F>(
+        &'g self,
+        key: K,
+        f: F,
+        guard: &'g Guard,
+    ) -> Option<&'g V>
+    where
+        F: FnMut(Option<&V>) -> Option<V>,
+    {
+        self.compute(key, f, Cursor::find_harris_michael, guard)
+    }
+
+    /// Omitted
+    pub fn harris_herlihy_shavit_compare_and_swap<'g>(
+        &'g self,
+        key: K,
+        expected: &V,
+        new: V,
+        guard: &'g Guard,
+    ) -> bool
+    where
+        V: PartialEq,
+    {
+        self.compare_and_swap(key, expected, new, Cursor::find_harris_michael, guard)
+    }
+
+    /// Looks up the value for `key`.
+    ///
+    /// It is wait-free: it traverses the list once without restarting, and the keys of the nodes
+    /// it visits strictly increase up to `key`, except for the replaced nodes.
+    pub fn kogan_petrank_lookup<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+        self.lookup(key, Cursor::find_harris_herlihy_shavit, guard)
+    }
+}
+
+impl<K, V> List<K, V>
+where
+    K: Ord + Clone,
+    V: Clone,
+{
+    /// Helps the announced insertions, if any.
+    #[inline]
+    fn help(&self, guard: &Guard) {
+        // SeqCst: pairs with the increment in `kogan_petrank_insert_inner`, so that the
+        // operations that start after an insertion is announced help it.
+        if self.announced.load(Ordering::SeqCst) != 0 {
+            self.help_announced(guard);
+        }
+    }
+
+    /// Helps the insertions in the announcement slots.
+    fn help_announced(&self, guard: &Guard) {
+        for slot in &self.announcements {
+            // SAFETY: the announcement is destroyed only after it is removed from the slot, and
+            // we are pinned.
+            if let Some(insertion) = unsafe { slot.load(Ordering::Acquire, guard).as_ref() } {
+                let _ = self.help_insert(insertion, guard);
+            }
+        }
+    }
+
+    /// Inserts a copy of the entry of `insertion` until it is completed, and returns the number
+    /// of attempts.
+    fn help_insert(&self, insertion: &Arc<Insertion<K, V>>, guard: &Guard) -> usize {
+        let mut node = None;
+        let mut attempts = 0;
+        while insertion.state.load(Ordering::Acquire) == PENDING {
+            attempts += 1;
+
+            let mut cursor = self.head(guard);
+            match cursor.find_harris_michael(&insertion.key, guard) {
+                Err(()) => continue,
+                Ok(true) => {
+                    // If the found node is a copy, it completed the insertion in `find`.
+                    // Otherwise, the key is present.
+                    let _ = insertion.state.compare_exchange(
+                        PENDING,
+                        FAILED,
+                        Ordering::AcqRel,
+                        Ordering::Relaxed,
+                    );
+                    continue;
+                }
+                Ok(false) => {}
+            }
+
+            let copy = node.take().unwrap_or_else(|| {
+                let mut copy = self.node(insertion.key.clone(), insertion.value.clone());
+                copy.insertion = Some(Arc::clone(insertion));
+                copy
+            });
+            match cursor.insert(copy, guard) {
+                Err(copy) => node = Some(copy),
+                Ok(()) => {
+                    // SAFETY: the cursor moved to the inserted copy.
+                    if unsafe { cursor.curr.deref() }.is_orphan() {
+                        // The insertion was completed after we found the key absent.
+                        let _ = cursor.delete(guard);
+                    }
+                }
+            }
+        }
+        attempts
+    }
+
+    /// Inserts a key-value pair, and returns whether it is inserted and the number of attempts.
+    fn kogan_petrank_insert_inner(&self, key: K, value: V, guard: &Guard) -> (bool, usize) {
+        self.help(guard);
+
+        let mut node = self.node(key, value);
+        for attempt in 1..=FAST_PATH_ATTEMPTS {
+            let mut cursor = self.head(guard);
+            match cursor.find_harris_michael(&node.key, guard) {
+                Err(()) => continue,
+                Ok(true) => return (false, attempt),
+                Ok(false) => {}
+            }
+
+            match cursor.insert(node, guard) {
+                Err(n) => node = n,
+                Ok(()) => return (true, attempt),
+            }
+        }
+
+        // Slow path: announce the insertion, so that the other updates help it before their own
+        // operations.
+        let Node { key, value, .. } = *node.into_box();
+        let insertion = Arc::new(Insertion {
+            key,
+            value,
+            state: AtomicUsize::new(PENDING),
+        });
+        let mut announcement = Owned::new(Arc::clone(&insertion));
+        let slot = 'claim: loop {
+            for slot in &self.announcements {
+                match slot.compare_exchange(
+                    Shared::null(),
+                    announcement,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                ) {
+                    Ok(_) => break 'claim slot,
+                    Err(e) => announcement = e.new,
+                }
+            }
+            // All slots are taken. Help the announced insertions to free them.
+            self.help_announced(guard);
+        };
+        // SeqCst: see `help`.
+        let _ = self.announced.fetch_add(1, Ordering::SeqCst);
+
+        let attempts = FAST_PATH_ATTEMPTS + self.help_insert(&insertion, guard);
+
+        let _ = self.announced.fetch_sub(1, Ordering::Release);
+        let announcement = slot.swap(Shared::null(), Ordering::Relaxed, guard);
+        // SAFETY: the announcement is removed from the slot, and the helpers that read it are
+        // pinned.
+        unsafe { guard.defer_destroy(announcement) };
+
+        let inserted = insertion.state.load(Ordering::Acquire) != FAILED;
+        (inserted, attempts)
+    }
+
+    /// Inserts a key-value pair if the key is not present.
+    ///
+    /// After a few failed attempts, it announces itself, and the other `kogan_petrank*` updates
+    /// help it by inserting copies of the entry before their own operations. Hence, it completes
+    /// in a bounded number of attempts if all the updates of the list are `kogan_petrank*`.
+    pub fn kogan_petrank_insert(&self, key: K, value: V, guard: &Guard) -> bool {
+        self.kogan_petrank_insert_inner(key, value, guard).0
+    }
+
+    /// Deletes the value for `key`, after helping the announced insertions.
+    pub fn kogan_petrank_delete<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+        self.help(guard);
+        self.delete(key, Cursor::find_harris_michael, guard)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::sync::atomic::AtomicBool;
+    use crossbeam_epoch::pin;
+    use std::thread::scope;
+
+    #[test]
+    fn iter_range() {
+        let list = List::new();
+        let guard = &pin();
+        for i in (0..100).rev() {
+            assert!(list.harris_insert(i, i * 10, guard));
+        }
+        for i in (0..100).step_by(2) {
+            assert_eq!(list.harris_michael_delete(&i, guard), Some(&(i * 10)));
+        }
+
+        let keys = list.iter(guard).map(|(k, _)| *k).collect::<Vec<_>>();
+        assert_eq!(keys, (1..100).step_by(2).collect::<Vec<_>>());
+        assert!(list.iter(guard).all(|(k, v)| *v == k * 10));
+
+        let keys = list
+            .range(10..20, guard)
+            .map(|(k, _)| *k)
+            .collect::<Vec<_>>();
+        assert_eq!(keys, [11, 13, 15, 17, 19]);
+        let keys = list.range(..=5, guard).map(|(k, _)| *k).collect::<Vec<_>>();
+        assert_eq!(keys, [1, 3, 5]);
+        let keys = list.range(95.., guard).map(|(k, _)| *k).collect::<Vec<_>>();
+        assert_eq!(keys, [95, 97, 99]);
+        assert_eq!(list.range(200.., guard).count(), 0);
+        let keys = list
+            .range((Bound::Excluded(11), Bound::Included(15)), guard)
+            .map(|(k, _)| *k)
+            .collect::<Vec<_>>();
+        assert_eq!(keys, [13, 15]);
+        let keys = list
+            .range((Bound::Excluded(10), Bound::Excluded(15)), guard)
+            .map(|(k, _)| *k)
+            .collect::<Vec<_>>();
+        assert_eq!(keys, [11, 13]);
+    }
+
+    #[test]
+    fn find_by() {
+        let list = List::new();
+        let guard = &pin();
+        for i in [10, 20, 30] {
+            assert!(list.harris_michael_insert(i, i, guard));
+        }
+
+        // Skip the node 20 as if it were equivalent to but not the target.
+        let mut cursor = list.head(guard);
+        let found = cursor.find_harris_michael_by(|k| k.cmp(&20).then(Less), guard);
+        assert_eq!(found, Ok(false));
+        assert_eq!(cursor.lookup(), Some(&30));
+
+        let mut cursor = list.head(guard);
+        assert_eq!(
+            cursor.find_harris_michael_by(|k| k.cmp(&20), guard),
+            Ok(true)
+        );
+        assert_eq!(cursor.lookup(), Some(&20));
+    }
+
+    #[test]
+    fn iter_concurrent() {
+        const COUNT: usize = 1000;
+        let list = List::new();
+        {
+            let guard = &pin();
+            for i in 0..COUNT {
+                assert!(list.harris_insert(2 * i, (), guard));
+            }
+        }
+
+        scope(|scope| {
+            // Odd keys come and go, while even keys stay.
+            scope.spawn(|| {
+                for i in 0..COUNT {
+                    let guard = &pin();
+                    assert!(list.harris_michael_insert(2 * i + 1, (), guard));
+                    assert!(list.harris_michael_delete(&(2 * i + 1), guard).is_some());
+                }
+            });
+
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..10 {
+                        let guard = &pin();
+                        let keys = list.iter(guard).help().map(|(k, _)| *k).collect::<Vec<_>>();
+                        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+                        assert_eq!(keys.iter().filter(|k| *k % 2 == 0).count(), COUNT);
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn update() {
+        let list = List::new();
+        let guard = &pin();
+        assert_eq!(list.harris_insert_or_replace(1, 10, guard), None);
+        assert_eq!(
+            list.harris_michael_insert_or_replace(1, 11, guard),
+            Some(&10)
+        );
+        assert_eq!(list.harris_herlihy_shavit_lookup(&1, guard), Some(&11));
+
+        assert!(!list.harris_compare_and_swap(1, &10, 12, guard));
+        assert!(list.harris_michael_compare_and_swap(1, &11, 12, guard));
+        assert!(!list.harris_herlihy_shavit_compare_and_swap(2, &11, 12, guard));
+        assert_eq!(list.harris_lookup(&1, guard), Some(&12));
+
+        assert_eq!(list.harris_compute(2, |v| v.map(|v| v + 1), guard), None);
+        assert_eq!(list.harris_michael_lookup(&2, guard), None);
+        assert_eq!(
+            list.harris_michael_compute(2, |_| Some(20), guard),
+            Some(&20)
+        );
+        assert_eq!(
+            list.harris_herlihy_shavit_compute(2, |v| v.map(|v| v + 1), guard),
+            Some(&21)
+        );
+        assert_eq!(list.harris_compute(1, |_| None, guard), None);
+        assert_eq!(list.harris_lookup(&1, guard), None);
+        let entries = list.iter(guard).map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+        assert_eq!(entries, [(2, 21)]);
+    }
+
+    #[test]
+    fn update_concurrent() {
+        const THREADS: usize = 4;
+        const COUNT: usize = 10_000;
+        let list = List::new();
+
+        scope(|scope| {
+            for t in 0..THREADS {
+                let list = &list;
+                scope.spawn(move || {
+                    for i in 0..COUNT {
+                        let guard = &pin();
+                        match (t + i) % 3 {
+                            0 => {
+                                list.harris_compute(0, |v| Some(v.map_or(1, |v| v + 1)), guard);
+                            }
+                            1 => {
+                                list.harris_michael_compute(
+                                    0,
+                                    |v| Some(v.map_or(1, |v| v + 1)),
+                                    guard,
+                                );
+                            }
+                            _ => loop {
+                                let curr = list.harris_herlihy_shavit_lookup(&0, guard).copied();
+                                let done = match curr {
+                                    Some(v) => list.harris_herlihy_shavit_compare_and_swap(
+                                        0,
+                                        &v,
+                                        v + 1,
+                                        guard,
+                                    ),
+                                    None => list.harris_michael_insert(0, 1, guard),
+                                };
+                                if done {
+                                    break;
+                                }
+                            },
+                        }
+                        // Replacements should never hide the key from lookups.
+                        assert!(list.harris_herlihy_shavit_lookup(&0, guard).is_some());
+                    }
+                });
+            }
+        });
+
+        let guard = &pin();
+        assert_eq!(list.harris_lookup(&0, guard), Some(&(THREADS * COUNT)));
+        assert_eq!(list.iter(guard).count(), 1);
+    }
+
+    #[test]
+    fn kogan_petrank_helping() {
+        let list = List::new();
+        let guard = &pin();
+
+        // Without contention, an insertion succeeds in the first attempt.
+        assert_eq!(list.kogan_petrank_insert_inner(0, 0, guard), (true, 1));
+        assert_eq!(list.kogan_petrank_insert_inner(0, 1, guard), (false, 1));
+        assert_eq!(list.announced.load(Ordering::Relaxed), 0);
+
+        // The other updates complete a stalled announced insertion.
+        let insertion = Arc::new(Insertion {
+            key: 1,
+            value: 1,
+            state: AtomicUsize::new(PENDING),
+        });
+        list.announcements[0].store(Owned::new(insertion.clone()), Ordering::Relaxed);
+        let _ = list.announced.fetch_add(1, Ordering::Relaxed);
+        assert_eq!(list.kogan_petrank_delete(&0, guard), Some(&0));
+        assert_eq!(list.kogan_petrank_lookup(&1, guard), Some(&1));
+        let state = insertion.state.load(Ordering::Relaxed);
+        assert!(state != PENDING && state != FAILED);
+        assert_eq!(list.help_insert(&insertion, guard), 0);
+
+        // A copy inserted after the insertion is completed is not found, and is marked.
+        assert_eq!(list.kogan_petrank_delete(&1, guard), Some(&1));
+        let mut copy = list.node(1, 2);
+        copy.insertion = Some(insertion.clone());
+        let mut cursor = list.head(guard);
+        assert_eq!(cursor.find_harris_michael(&1, guard), Ok(false));
+        assert!(cursor.insert(copy, guard).is_ok());
+        assert_eq!(list.kogan_petrank_lookup(&1, guard), None);
+        assert_eq!(list.iter(guard).count(), 0);
+        assert!(list.harris_michael_insert(1, 3, guard));
+        assert_eq!(list.iter(guard).collect::<Vec<_>>(), [(&1, &3)]);
+
+        let _ = list.announced.fetch_sub(1, Ordering::Relaxed);
+        let announcement = list.announcements[0].swap(Shared::null(), Ordering::Relaxed, guard);
+        unsafe { guard.defer_destroy(announcement) };
+    }
+
+    #[test]
+    fn kogan_petrank_bounded() {
+        const KEYS: usize = 16;
+        const COUNT: usize = 10_000;
+        const DELETERS: usize = 3;
+        // After an insertion is announced, each of its failed attempts is caused by another
+        // successful CAS. The deleters make at most one operation of their own, and then help
+        // the insertion until it completes. Each marks and inserts at most one node in total,
+        // there is at most one node of each key in the list, and each marked node is unlinked
+        // once.
+        const BOUND: usize = FAST_PATH_ATTEMPTS + KEYS + 6 * DELETERS + 3;
+        let list = List::new();
+
+        let done = AtomicBool::new(false);
+        scope(|scope| {
+            for _ in 0..DELETERS {
+                scope.spawn(|| {
+                    while !done.load(Ordering::Relaxed) {
+                        for key in 0..KEYS {
+                            let guard = &pin();
+                            let _ = list.kogan_petrank_delete(&key, guard);
+                        }
+                    }
+                });
+            }
+
+            for i in 0..COUNT {
+                let guard = &pin();
+                let (_, attempts) = list.kogan_petrank_insert_inner(i % KEYS, i, guard);
+                assert!(attempts <= BOUND, "{attempts} attempts");
+                let _ = list.kogan_petrank_lookup(&(i % KEYS), guard);
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+        assert_eq!(list.announced.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn linearizable() {
+        use crate::lockfree::linearizability::{check_partitioned, record, Spec};
+
+        /// The value of a single key, as the history is partitioned by keys.
+        #[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+        struct Entry(Option<u64>);
+
+        #[derive(Debug)]
+        enum Op {
+            Insert(u64, u64),
+            Delete(u64),
+            Lookup(u64),
+        }
+
+        #[derive(Debug, PartialEq)]
+        enum Ret {
+            Inserted(bool),
+            Value(Option<u64>),
+        }
+
+        impl Spec for Entry {
+            type Op = Op;
+            type Ret = Ret;
+
+            fn apply(&mut self, op: &Op) -> Ret {
+                match *op {
+                    Op::Insert(_, v) => {
+                        let inserted = self.0.is_none();
+                        if inserted {
+                            self.0 = Some(v);
+                        }
+                        Ret::Inserted(inserted)
+                    }
+                    Op::Delete(_) => Ret::Value(self.0.take()),
+                    Op::Lookup(_) => Ret::Value(self.0),
+                }
+            }
+        }
+
+        type Exec = fn(&List<u64, u64>, &Op, &Guard) -> Ret;
+        let strategies: [(&str, Exec); 3] = [
+            ("harris", |list, op, guard| match *op {
+                Op::Insert(k, v) => Ret::Inserted(list.harris_insert(k, v, guard)),
+                Op::Delete(k) => Ret::Value(list.harris_delete(&k, guard).copied()),
+                Op::Lookup(k) => Ret::Value(list.harris_lookup(&k, guard).copied()),
+            }),
+            ("harris_michael", |list, op, guard| match *op {
+                Op::Insert(k, v) => Ret::Inserted(list.harris_michael_insert(k, v, guard)),
+                Op::Delete(k) => Ret::Value(list.harris_michael_delete(&k, guard).copied()),
+                Op::Lookup(k) => Ret::Value(list.harris_michael_lookup(&k, guard).copied()),
+            }),
+            ("harris_herlihy_shavit", |list, op, guard| match *op {
+                Op::Insert(k, v) => Ret::Inserted(list.harris_herlihy_shavit_insert(k, v, guard)),
+                Op::Delete(k) => Ret::Value(list.harris_herlihy_shavit_delete(&k, guard).copied()),
+                Op::Lookup(k) => Ret::Value(list.harris_herlihy_shavit_lookup(&k, guard).copied()),
+            }),
+        ];
+
+        for (name, exec) in strategies {
+            for seed in 0..20 {
+                let list = List::new();
+                let history = record(
+                    4,
+                    256,
+                    seed,
+                    |_, rng| {
+                        let key = rng.next() % 8;
+                        match rng.next() % 3 {
+                            0 => Op::Insert(key, rng.next() % 8),
+                            1 => Op::Delete(key),
+                            _ => Op::Lookup(key),
+                        }
+                    },
+                    |op| exec(&list, op, &pin()),
+                );
+                let linearizable = check_partitioned(Entry::default(), &history, |op| match *op {
+                    Op::Insert(k, _) | Op::Delete(k) | Op::Lookup(k) => k,
+                });
+                assert!(linearizable, "{name}: {history:#?}");
+            }
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats() {
+        const COUNT: usize = 1_000;
+        let list = List::new();
+        let counters = list.counters.clone();
+
+        // The guard is held throughout, so no retired node is freed and the counts are exact.
+        let guard = &pin();
+        for i in 0..COUNT {
+            assert!(list.harris_insert(i, i, guard));
+        }
+
+        // An insertion that finds the key frees its node right away, since it was never shared.
+        assert!(!list.harris_insert(0, 0, guard));
+        assert_eq!(
+            counters.stats(),
+            Stats {
+                allocated: COUNT + 1,
+                retired: 0,
+                freed: 1
+            }
+        );
+
+        // Logically delete the keys 1, 2 and 3 by marking their nodes, like a deleter that stalls
+        // before unlinking them.
+        let mut curr = list.head.load(Ordering::Acquire, guard);
+        while let Some(node) = unsafe { curr.as_ref() } {
+            if (1..=3).contains(&node.key) {
+                let _ = node.next.fetch_or(1, Ordering::Release, guard);
+            }
+            curr = node.next.load(Ordering::Acquire, guard).with_tag(0);
+        }
+
+        // The wait-free lookup skips the marked nodes without unlinking them.
+        assert_eq!(list.harris_herlihy_shavit_lookup(&2, guard), None);
+        assert_eq!(list.harris_herlihy_shavit_lookup(&4, guard), Some(&4));
+        assert_eq!(counters.stats().retired, 0);
+
+        // Harris's traversal unlinks the whole marked chain with a single CAS and retires it.
+        assert_eq!(list.harris_lookup(&4, guard), Some(&4));
+        assert_eq!(counters.stats().retired, 3);
+        assert_eq!(list.harris_michael_delete(&2, guard), None);
+        assert_eq!(counters.stats().retired, 3);
+
+        // A replacement retires the replaced node.
+        assert_eq!(list.harris_michael_insert_or_replace(4, 5, guard), Some(&4));
+        assert_eq!(
+            counters.stats(),
+            Stats {
+                allocated: COUNT + 2,
+                retired: 4,
+                freed: 1
+            }
+        );
+
+        // Dropping the list frees the nodes still in it right away, without retiring them.
+        drop(list);
+        assert_eq!(
+            counters.stats(),
+            Stats {
+                allocated: COUNT + 2,
+                retired: 4,
+                freed: 1 + COUNT - 3
+            }
+        );
+    }
 }
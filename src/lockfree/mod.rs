@@ -1,9 +1,19 @@
 //! Lock-free data structures.
 
+pub mod channel;
+mod deque;
+#[cfg(test)]
+mod linearizability;
 pub mod list;
+mod priority_queue;
 mod queue;
+mod rng;
 mod stack;
+mod stats;
 
+pub use deque::{Steal, Stealer, Worker};
 pub use list::List;
-pub use queue::Queue;
-pub use stack::Stack;
+pub use priority_queue::PriorityQueue;
+pub use queue::{Drain as QueueDrain, Queue};
+pub use stack::{Drain as StackDrain, Stack};
+pub use stats::Stats;
@@ -0,0 +1,483 @@
+//! Lock-free skiplist-based priority queue of Lindén and Jonsson.
+//!
+//! Lindén and Jonsson.  A Skiplist-Based Concurrent Priority Queue with Minimal Memory
+//! Contention.  OPODIS 2013.  <https://doi.org/10.1007/978-3-319-03850-6_15>
+//!
+//! `pop_min()` logically deletes a node by marking the level-0 pointer *to* it, so the deleted nodes
+//! always form a prefix of the list. Hence, `pop_min()` mostly touches a single pointer with
+//! `fetch_or`, and the prefix is physically unlinked in batches by swinging the heads.
+//!
+//! `push()` finds its position through the skiplist index in expected O(log n) steps, and the
+//! upper levels skip the deleted prefix. The node is inserted after the nodes with the same
+//! priority, so that they are popped in FIFO order.
+
+use core::cell::Cell;
+use core::mem::{self, ManuallyDrop};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crossbeam_epoch::{Atomic, Guard, Owned, Shared};
+
+use super::rng::Rng;
+
+/// Maximum height of the nodes.
+const HEIGHT: usize = 16;
+
+/// Length of the deleted prefix that triggers its physical deletion.
+const BOUND_OFFSET: usize = 32;
+
+/// Lock-free priority queue that pops the entry with the smallest priority.
+///
+/// Entries with the same priority are popped in FIFO order.
+#[derive(Debug)]
+pub struct PriorityQueue<P, V> {
+    /// The pointers to the first node of each level. `head[0]` is marked if the first node is
+    /// deleted.
+    head: [Atomic<Node<P, V>>; HEIGHT],
+}
+
+#[derive(Debug)]
+struct Node<P, V> {
+    priority: P,
+    /// Taken by the thread that deleted the node.
+    value: ManuallyDrop<V>,
+    /// Set while `push()` may still link the node into the upper levels. The deleted prefix is not
+    /// freed from such a node on.
+    inserting: AtomicBool,
+    /// The next nodes at each level. Only `next[0]` is marked: the next node is deleted.
+    next: Box<[Atomic<Node<P, V>>]>,
+}
+
+// Any particular `V` should never be accessed concurrently, so no need for `V: Sync`. Priorities
+// are shared by `peek_min()`.
+unsafe impl<P: Send + Sync, V: Send> Send for PriorityQueue<P, V> {}
+unsafe impl<P: Send + Sync, V: Send> Sync for PriorityQueue<P, V> {}
+
+impl<P, V> Default for PriorityQueue<P, V> {
+    fn default() -> Self {
+        Self {
+            head: Default::default(),
+        }
+    }
+}
+
+/// Returns a random height of a node, 1 with probability 1/2, 2 with probability 1/4, and so on.
+fn random_height() -> usize {
+    thread_local! {
+        static RNG: Cell<Option<Rng>> = Cell::new(None);
+    }
+
+    RNG.with(|rng| {
+        // Seeded by the address of the thread-local state.
+        let mut state = rng
+            .get()
+            .unwrap_or_else(|| Rng::new(rng as *const Cell<Option<Rng>> as usize as u64));
+        let x = state.next();
+        rng.set(Some(state));
+        (x.trailing_ones() as usize + 1).min(HEIGHT)
+    })
+}
+
+impl<P: Ord, V> PriorityQueue<P, V> {
+    /// Creates a new, empty priority queue.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Finds the position of a node with the given priority at each level: `preds[i]` is the
+    /// pointer at level `i` of the last node with a priority not larger than `priority`, skipping
+    /// the deleted prefix, and `succs[i]` is its value. Returns the last deleted node passed at
+    /// level 0, or null.
+    fn locate<'g>(
+        &'g self,
+        priority: &P,
+        preds: &mut [&'g Atomic<Node<P, V>>; HEIGHT],
+        succs: &mut [Shared<'g, Node<P, V>>; HEIGHT],
+        guard: &'g Guard,
+    ) -> Shared<'g, Node<P, V>> {
+        let mut pred: &'g [Atomic<Node<P, V>>] = &self.head;
+        let mut deleted = Shared::null();
+
+        for level in (0..HEIGHT).rev() {
+            let mut curr = pred[level].load(Ordering::Acquire, guard);
+            loop {
+                // SAFETY: nodes are not freed while the guard is alive.
+                let Some(curr_ref) = (unsafe { curr.with_tag(0).as_ref() }) else {
+                    break;
+                };
+                // Only the pointers at level 0 are marked. A node whose next node is deleted is
+                // also deleted, but not the last one in the prefix.
+                if curr.tag() != 0 {
+                    deleted = curr.with_tag(0);
+                } else if curr_ref.priority > *priority
+                    && curr_ref.next[0].load(Ordering::Acquire, guard).tag() == 0
+                {
+                    break;
+                }
+                pred = &curr_ref.next;
+                curr = pred[level].load(Ordering::Acquire, guard);
+            }
+            preds[level] = &pred[level];
+            succs[level] = curr;
+        }
+
+        deleted
+    }
+
+    /// Inserts `value` with the given priority.
+    pub fn push(&self, priority: P, value: V, guard: &Guard) {
+        let height = random_height();
+        let mut node = Owned::new(Node {
+            priority,
+            value: ManuallyDrop::new(value),
+            inserting: AtomicBool::new(true),
+            next: (0..height).map(|_| Atomic::null()).collect(),
+        });
+        let mut preds = [&self.head[0]; HEIGHT];
+        let mut succs = [Shared::null(); HEIGHT];
+
+        // The node is inserted once it is linked at level 0. Since `succs[0]` is not marked, the
+        // CAS fails if it is deleted, so we never insert into the deleted prefix.
+        let (node, mut deleted) = loop {
+            let deleted = self.locate(&node.priority, &mut preds, &mut succs, guard);
+            node.next[0].store(succs[0], Ordering::Relaxed);
+            match preds[0].compare_exchange(
+                succs[0],
+                node,
+                Ordering::Release,
+                Ordering::Relaxed,
+                guard,
+            ) {
+                Ok(node) => break (node, deleted),
+                Err(e) => node = e.new,
+            }
+        };
+        // SAFETY: the node is not freed while `inserting` is set.
+        let node_ref = unsafe { node.deref() };
+
+        // The upper levels are linked on a best-effort basis. Stop if the node or its successor is
+        // deleted, since the upper levels of the deleted prefix may be already restructured.
+        let mut level = 1;
+        while level < height {
+            if node_ref.next[0].load(Ordering::Acquire, guard).tag() != 0 {
+                break;
+            }
+            let succ = succs[level];
+            // SAFETY: nodes are not freed while the guard is alive.
+            if let Some(succ_ref) = unsafe { succ.as_ref() } {
+                if succ == deleted || succ_ref.next[0].load(Ordering::Acquire, guard).tag() != 0 {
+                    break;
+                }
+            }
+
+            node_ref.next[level].store(succ, Ordering::Relaxed);
+            if preds[level]
+                .compare_exchange(succ, node, Ordering::Release, Ordering::Relaxed, guard)
+                .is_ok()
+            {
+                level += 1;
+                continue;
+            }
+
+            // Stop if a node with the same priority is inserted after this node, since linking
+            // this node after it would break the order of the upper level.
+            deleted = self.locate(&node_ref.priority, &mut preds, &mut succs, guard);
+            if !ptr::eq(preds[0], &node_ref.next[0]) {
+                break;
+            }
+        }
+
+        node_ref.inserting.store(false, Ordering::Release);
+    }
+
+    /// Removes the entry with the smallest priority.
+    ///
+    /// Returns `None` if the queue is observed to be empty.
+    pub fn pop_min(&self, guard: &Guard) -> Option<(P, V)>
+    where
+        P: Clone,
+    {
+        let head = self.head[0].load(Ordering::Acquire, guard);
+        let mut prev = &self.head[0];
+        let mut curr = head;
+        let mut offset = 0;
+        // The first node in the deleted prefix that `push()` may still link into upper levels.
+        let mut inserting = Shared::null();
+
+        let node = loop {
+            if curr.tag() == 0 {
+                if curr.is_null() {
+                    return None;
+                }
+                // A non-null pointer never becomes null again, so we don't mark a null pointer.
+                // AcqRel: to acquire the node that may have been inserted after we loaded `curr`.
+                curr = prev.fetch_or(1, Ordering::AcqRel, guard);
+                if curr.tag() == 0 {
+                    break curr;
+                }
+            }
+
+            // The next node is deleted, so skip it.
+            // SAFETY: a marked pointer is not null, and nodes are not freed while the guard is
+            // alive.
+            let curr_ref = unsafe { curr.with_tag(0).deref() };
+            if inserting.is_null() && curr_ref.inserting.load(Ordering::Acquire) {
+                inserting = curr.with_tag(0);
+            }
+            prev = &curr_ref.next[0];
+            curr = prev.load(Ordering::Acquire, guard);
+            offset += 1;
+        };
+
+        // SAFETY: we deleted `node` by marking the pointer to it, so we own its value.
+        let node_ref = unsafe { node.deref() };
+        let value = ManuallyDrop::into_inner(unsafe { ptr::read(&node_ref.value) });
+        let priority = node_ref.priority.clone();
+
+        if offset >= BOUND_OFFSET && head.tag() != 0 {
+            let last = if inserting.is_null() { node } else { inserting };
+            self.restructure(head, last, guard);
+        }
+
+        Some((priority, value))
+    }
+
+    /// Physically deletes the prefix from `head` to `last` (exclusive) by making the head point to
+    /// `last`, if the head is still `head`.
+    fn restructure(
+        &self,
+        head: Shared<'_, Node<P, V>>,
+        last: Shared<'_, Node<P, V>>,
+        guard: &Guard,
+    ) {
+        if self.head[0]
+            .compare_exchange(
+                head,
+                last.with_tag(1),
+                Ordering::Release,
+                Ordering::Relaxed,
+                guard,
+            )
+            .is_err()
+        {
+            return;
+        }
+
+        // Make the upper heads skip the deleted prefix, except its last node. Since the levels are
+        // ordered in the same way, no node at an upper level points into the prefix from then on.
+        let mut pred: &[Atomic<Node<P, V>>] = &self.head;
+        let mut level = HEIGHT - 1;
+        while level > 0 {
+            let first = self.head[level].load(Ordering::Acquire, guard);
+            // SAFETY: nodes are not freed while the guard is alive.
+            match unsafe { first.as_ref() } {
+                Some(first_ref) if first_ref.next[0].load(Ordering::Acquire, guard).tag() != 0 => {}
+                _ => {
+                    level -= 1;
+                    continue;
+                }
+            }
+
+            let mut curr = pred[level].load(Ordering::Acquire, guard);
+            // SAFETY: nodes are not freed while the guard is alive.
+            while let Some(curr_ref) = unsafe { curr.as_ref() } {
+                if curr_ref.next[0].load(Ordering::Acquire, guard).tag() == 0 {
+                    break;
+                }
+                pred = &curr_ref.next;
+                curr = pred[level].load(Ordering::Acquire, guard);
+            }
+            if self.head[level]
+                .compare_exchange(first, curr, Ordering::Release, Ordering::Relaxed, guard)
+                .is_ok()
+            {
+                level -= 1;
+            }
+        }
+
+        let mut node = head.with_tag(0);
+        while node != last {
+            // SAFETY: the nodes in the deleted prefix are not null, and their `next[0]` pointers
+            // are marked so they never change.
+            let next = unsafe { node.deref() }.next[0].load(Ordering::Relaxed, guard);
+            // SAFETY: we unlinked the prefix with the above CASes, and the values in it are already
+            // taken.
+            unsafe { guard.defer_destroy(node) };
+            node = next.with_tag(0);
+        }
+    }
+
+    /// Returns the smallest priority in the queue.
+    ///
+    /// The value is not returned, since it may be taken by a concurrent `pop_min()`.
+    pub fn peek_min<'g>(&'g self, guard: &'g Guard) -> Option<&'g P> {
+        let mut curr = self.head[0].load(Ordering::Acquire, guard);
+        loop {
+            // SAFETY: nodes are not freed while the guard is alive.
+            let curr_ref = unsafe { curr.with_tag(0).as_ref() }?;
+            if curr.tag() == 0 {
+                return Some(&curr_ref.priority);
+            }
+            curr = curr_ref.next[0].load(Ordering::Acquire, guard);
+        }
+    }
+
+    /// Returns `true` if the queue is empty.
+    pub fn is_empty(&self, guard: &Guard) -> bool {
+        self.peek_min(guard).is_none()
+    }
+}
+
+impl<P, V> Drop for PriorityQueue<P, V> {
+    fn drop(&mut self) {
+        // The upper levels only point to the nodes at level 0.
+        let mut o_curr = mem::take(&mut self.head[0]);
+
+        // SAFETY: All non-null nodes made were valid, and we have unique ownership via `&mut self`.
+        while let Some(curr) = unsafe { o_curr.try_into_owned() } {
+            let deleted = curr.tag() != 0;
+            let mut curr = curr.into_box();
+            if !deleted {
+                // SAFETY: the node is not deleted, so it still owns its value.
+                unsafe { ManuallyDrop::drop(&mut curr.value) };
+            }
+            o_curr = mem::take(&mut curr.next[0]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::rng::Rng;
+    use super::*;
+    use core::cmp::Reverse;
+    use crossbeam_epoch::pin;
+    use std::collections::{BinaryHeap, HashMap};
+    use std::thread::scope;
+
+    #[test]
+    fn sequential_against_binary_heap() {
+        let queue = PriorityQueue::new();
+        let mut heap = BinaryHeap::new();
+        let mut rng = Rng::new(0x2545_f491_4f6c_dd1d);
+
+        for seq in 0..100_000 {
+            let guard = &pin();
+            match rng.next() % 2 {
+                0 => {
+                    let priority = rng.next() % 64;
+                    queue.push(priority, seq, guard);
+                    // `seq` breaks ties in FIFO order.
+                    heap.push(Reverse((priority, seq)));
+                }
+                _ => {
+                    let expected = heap.pop().map(|Reverse(e)| e);
+                    assert_eq!(queue.pop_min(guard), expected);
+                }
+            }
+            assert_eq!(queue.peek_min(guard), heap.peek().map(|Reverse((p, _))| p));
+        }
+    }
+
+    #[test]
+    fn concurrent_push_pop() {
+        const THREADS: u64 = 4;
+        const COUNT: u64 = 2_000;
+        let queue = PriorityQueue::new();
+
+        let popped = scope(|scope| {
+            let handles = (0..THREADS)
+                .map(|t| {
+                    let queue = &queue;
+                    scope.spawn(move || {
+                        let mut rng = Rng::new(t);
+                        let mut popped = Vec::new();
+                        for i in 0..COUNT {
+                            let guard = &pin();
+                            queue.push(rng.next() % 128, t * COUNT + i, guard);
+                            if i % 2 == 0 {
+                                popped.extend(queue.pop_min(guard).map(|(_, v)| v));
+                            }
+                        }
+                        popped
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        // The rest should be popped in the order of priorities.
+        let guard = &pin();
+        let mut rest = Vec::new();
+        while let Some((priority, value)) = queue.pop_min(guard) {
+            if let Some((last, _)) = rest.last() {
+                assert!(*last <= priority);
+            }
+            rest.push((priority, value));
+        }
+
+        let mut all = popped
+            .into_iter()
+            .chain(rest.into_iter().map(|(_, v)| v))
+            .collect::<Vec<_>>();
+        all.sort_unstable();
+        assert_eq!(all, (0..THREADS * COUNT).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn concurrent_levels() {
+        const THREADS: u64 = 4;
+        const COUNT: u64 = 5_000;
+        let queue = PriorityQueue::new();
+
+        scope(|scope| {
+            for t in 0..THREADS {
+                let queue = &queue;
+                scope.spawn(move || {
+                    let mut rng = Rng::new(t);
+                    for i in 0..COUNT {
+                        let guard = &pin();
+                        queue.push(rng.next() % 1024, i, guard);
+                        if i % 3 == 0 {
+                            let _ = queue.pop_min(guard);
+                        }
+                    }
+                });
+            }
+        });
+
+        // Every upper level is a subsequence of level 0.
+        let guard = &pin();
+        let mut positions = HashMap::new();
+        let mut curr = queue.head[0].load(Ordering::Acquire, guard);
+        while let Some(curr_ref) = unsafe { curr.with_tag(0).as_ref() } {
+            let _ = positions.insert(curr.with_tag(0).as_raw(), positions.len());
+            curr = curr_ref.next[0].load(Ordering::Acquire, guard);
+        }
+        for level in 1..HEIGHT {
+            let mut last = None;
+            let mut curr = queue.head[level].load(Ordering::Acquire, guard);
+            while let Some(curr_ref) = unsafe { curr.as_ref() } {
+                let position = positions[&curr.as_raw()];
+                assert!(last < Some(position));
+                last = Some(position);
+                curr = curr_ref.next[level].load(Ordering::Acquire, guard);
+            }
+        }
+    }
+
+    #[test]
+    fn drop_values() {
+        let queue = PriorityQueue::new();
+        let guard = &pin();
+        for i in 0..100 {
+            queue.push(i % 7, i.to_string(), guard);
+        }
+        for _ in 0..50 {
+            assert!(queue.pop_min(guard).is_some());
+        }
+    }
+}
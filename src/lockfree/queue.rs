@@ -5,20 +5,40 @@
 //! Michael and Scott.  Simple, Fast, and Practical Non-Blocking and Blocking Concurrent Queue
 //! Algorithms.  PODC 1996.  <http://dl.acm.org/citation.cfm?id=248106>
 
+use core::fmt;
+use core::marker::PhantomData;
 use core::mem::{self, MaybeUninit};
 use core::sync::atomic::Ordering;
 
-use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Shared};
+use crossbeam_epoch::{unprotected, Atomic, Owned, Shared};
 use crossbeam_utils::CachePadded;
 
-/// Michael-Scott queue.
+#[cfg(feature = "stats")]
+use super::stats::Stats;
+use super::stats::{Counters, Tracker};
+use crate::reclaim::{Epoch, Reclaimer};
+
+/// Michael-Scott queue, whose nodes are reclaimed with the memory reclamation scheme `R`.
 // The representation here is a singly-linked list, with a sentinel node at the front. In general
 // the `tail` pointer may lag behind the actual tail. Non-sentinel nodes are either all `Data` or
 // all `Blocked` (requests for data from blocked threads).
 #[derive(Debug)]
-pub struct Queue<T> {
+pub struct Queue<T, R: Reclaimer = Epoch> {
     head: CachePadded<Atomic<Node<T>>>,
     tail: CachePadded<Atomic<Node<T>>>,
+    counters: Counters,
+    _marker: PhantomData<R>,
+}
+
+/// Iterator over the values taken from a [`Queue`] by [`Queue::drain`], from front to back.
+///
+/// The values that are not yielded are dropped when the iterator is dropped.
+pub struct Drain<'g, T, R: Reclaimer = Epoch> {
+    /// The detached node whose next node holds the next value.
+    sentinel: Shared<'g, Node<T>>,
+    /// The last detached node, which is the sentinel of the queue now.
+    last: Shared<'g, Node<T>>,
+    guard: &'g R::Guard,
 }
 
 #[derive(Debug)]
@@ -32,21 +52,26 @@ struct Node<T> {
     data: MaybeUninit<T>,
 
     next: Atomic<Node<T>>,
+
+    tracker: Tracker,
 }
 
 // Any particular `T` should never be accessed concurrently, so no need for `Sync`.
-unsafe impl<T: Send> Sync for Queue<T> {}
-unsafe impl<T: Send> Send for Queue<T> {}
+unsafe impl<T: Send, R: Reclaimer> Sync for Queue<T, R> {}
+unsafe impl<T: Send, R: Reclaimer> Send for Queue<T, R> {}
 
-impl<T> Default for Queue<T> {
+impl<T, R: Reclaimer> Default for Queue<T, R> {
     fn default() -> Self {
         let q = Self {
             head: CachePadded::new(Atomic::null()),
             tail: CachePadded::new(Atomic::null()),
+            counters: Counters::default(),
+            _marker: PhantomData,
         };
         let sentinel = Owned::new(Node {
             data: MaybeUninit::uninit(),
             next: Atomic::null(),
+            tracker: Tracker::untracked(),
         });
         // SAFETY: We are creating a new queue, hence have sole ownership of it.
         let sentinel = sentinel.into_shared(unsafe { unprotected() });
@@ -61,22 +86,27 @@ impl<T> Queue<T> {
     pub fn new() -> Self {
         Self::default()
     }
+}
 
+impl<T, R: Reclaimer> Queue<T, R> {
     /// Adds `t` to the back of the queue, possibly waking up threads blocked on `pop()`.
-    pub fn push(&self, t: T, guard: &Guard) {
+    pub fn push(&self, t: T, guard: &R::Guard) {
         let new = Owned::new(Node {
             data: MaybeUninit::new(t),
             next: Atomic::null(),
+            tracker: self.counters.track(),
         });
-        let new = new.into_shared(guard);
+        // `new` is protected since it may be popped and retired before we swing the tail to it.
+        let new = R::protect_owned(new, guard);
+        let atomic_guard = R::atomic_guard(guard);
 
         loop {
             // We push onto the tail, so we'll start optimistically by looking there first.
-            let tail = self.tail.load(Ordering::Acquire, guard);
+            let tail = R::protect(&*self.tail, Ordering::Acquire, guard);
 
             // Attempt to push onto the `tail` snapshot; fails if `tail.next` has changed.
             let tail_ref = unsafe { tail.deref() };
-            let next = tail_ref.next.load(Ordering::Acquire, guard);
+            let next = tail_ref.next.load(Ordering::Acquire, atomic_guard);
 
             // If `tail` is not the actual tail, try to "help" by moving the tail pointer forward.
             if !next.is_null() {
@@ -85,8 +115,10 @@ impl<T> Queue<T> {
                     next,
                     Ordering::Release,
                     Ordering::Relaxed,
-                    guard,
+                    atomic_guard,
                 );
+                // SAFETY: we no longer access `tail`.
+                unsafe { R::release(tail, guard) };
                 continue;
             }
 
@@ -98,7 +130,7 @@ impl<T> Queue<T> {
                     new,
                     Ordering::Release,
                     Ordering::Relaxed,
-                    guard,
+                    atomic_guard,
                 )
                 .is_ok()
             {
@@ -108,39 +140,69 @@ impl<T> Queue<T> {
                     new,
                     Ordering::Release,
                     Ordering::Relaxed,
-                    guard,
+                    atomic_guard,
                 );
+                // SAFETY: we no longer access `tail` and `new`.
+                unsafe {
+                    R::release(tail, guard);
+                    R::release(new, guard);
+                }
                 break;
             }
+
+            // SAFETY: we no longer access `tail`.
+            unsafe { R::release(tail, guard) };
         }
     }
 
     /// Attempts to dequeue from the front.
     ///
     /// Returns `None` if the queue is observed to be empty.
-    pub fn try_pop(&self, guard: &Guard) -> Option<T> {
+    pub fn try_pop(&self, guard: &R::Guard) -> Option<T> {
+        let atomic_guard = R::atomic_guard(guard);
         loop {
-            let head = self.head.load(Ordering::Acquire, guard);
-            let next = unsafe { head.deref() }.next.load(Ordering::Acquire, guard);
+            let head = R::protect(&*self.head, Ordering::Acquire, guard);
+            let next = R::protect(&unsafe { head.deref() }.next, Ordering::Acquire, guard);
+
+            // `next` is protected only if it's not retired when `head` still points to it, i.e. if
+            // `head` is still the sentinel.
+            if self.head.load(Ordering::Acquire, atomic_guard) != head {
+                // SAFETY: we no longer access `head` and `next`.
+                unsafe {
+                    R::release(head, guard);
+                    R::release(next, guard);
+                }
+                continue;
+            }
 
-            let next_ref = unsafe { next.as_ref() }?;
+            let Some(next_ref) = (unsafe { next.as_ref() }) else {
+                // SAFETY: we no longer access `head`.
+                unsafe { R::release(head, guard) };
+                return None;
+            };
 
             // Moves `tail` if it's stale. Relaxed load is enough because if tail == head, then the
             // messages for that node are already acquired.
-            let tail = self.tail.load(Ordering::Relaxed, guard);
+            let tail = self.tail.load(Ordering::Relaxed, atomic_guard);
             if tail == head {
                 let _ = self.tail.compare_exchange(
                     tail,
                     next,
                     Ordering::Release,
                     Ordering::Relaxed,
-                    guard,
+                    atomic_guard,
                 );
             }
 
             if self
                 .head
-                .compare_exchange(head, next, Ordering::Release, Ordering::Relaxed, guard)
+                .compare_exchange(
+                    head,
+                    next,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    atomic_guard,
+                )
                 .is_ok()
             {
                 // Since the above `compare_exchange()` succeeded, `head` is detached from `self` so
@@ -158,15 +220,149 @@ impl<T> Queue<T> {
                 // SAFETY: `head` is unreachable, and we no longer access `head`. We destroy `head`
                 // after the final access to `next` above to ensure that `next` is also destroyed
                 // after.
-                unsafe { guard.defer_destroy(head) };
+                unsafe {
+                    head.deref().tracker.retire();
+                    R::retire(head, guard);
+                    R::release(head, guard);
+                    R::release(next, guard);
+                }
 
                 return Some(result);
             }
+
+            // SAFETY: we no longer access `head` and `next`.
+            unsafe {
+                R::release(head, guard);
+                R::release(next, guard);
+            }
+        }
+    }
+
+    /// Takes all the values from the queue at once.
+    ///
+    /// The values are detached in a single CAS that moves the head to the tail, so the returned
+    /// iterator yields the values that were in the queue at that moment, even if other threads
+    /// concurrently push and pop.
+    pub fn drain<'g>(&self, guard: &'g R::Guard) -> Drain<'g, T, R> {
+        let atomic_guard = R::atomic_guard(guard);
+        loop {
+            let head = R::protect(&*self.head, Ordering::Acquire, guard);
+            let tail = R::protect(&*self.tail, Ordering::Acquire, guard);
+            let next = unsafe { tail.deref() }
+                .next
+                .load(Ordering::Acquire, atomic_guard);
+
+            // Moves `tail` to the actual tail, so that everything up to it is detached.
+            if !next.is_null() {
+                let _ = self.tail.compare_exchange(
+                    tail,
+                    next,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    atomic_guard,
+                );
+                // SAFETY: we no longer access `head` and `tail`.
+                unsafe {
+                    R::release(head, guard);
+                    R::release(tail, guard);
+                }
+                continue;
+            }
+
+            // `tail` is never behind `head`, so if the CAS succeeds, `tail` was in the queue and
+            // becomes the new sentinel. The nodes from `head` to `tail` (exclusive) are detached.
+            if head == tail
+                || self
+                    .head
+                    .compare_exchange(
+                        head,
+                        tail,
+                        Ordering::Release,
+                        Ordering::Relaxed,
+                        atomic_guard,
+                    )
+                    .is_ok()
+            {
+                // SAFETY: the detached nodes are retired only by the iterator, so `head` need not be
+                // protected anymore. `tail` stays protected until the iterator is dropped.
+                unsafe { R::release(head, guard) };
+                return Drain {
+                    sentinel: head,
+                    last: tail,
+                    guard,
+                };
+            }
+
+            // SAFETY: we no longer access `head` and `tail`.
+            unsafe {
+                R::release(head, guard);
+                R::release(tail, guard);
+            }
+        }
+    }
+
+    /// Removes all the values from the queue at once.
+    ///
+    /// See [`Queue::drain`].
+    pub fn clear(&self, guard: &R::Guard) {
+        self.drain(guard).for_each(drop);
+    }
+
+    /// Returns the memory reclamation statistics of the queue.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.counters.stats()
+    }
+}
+
+impl<'g, T, R: Reclaimer> Iterator for Drain<'g, T, R> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.sentinel == self.last {
+            return None;
         }
+
+        // SAFETY: the detached nodes are retired only by this iterator, and `last` is protected.
+        // `sentinel` is not `last`, so its next node is detached as well, and not null.
+        unsafe {
+            let sentinel_ref = self.sentinel.deref();
+            let next = sentinel_ref
+                .next
+                .load(Ordering::Acquire, R::atomic_guard(self.guard));
+
+            // The values of the detached nodes are ours. Like in `try_pop()`, `next` becomes the
+            // sentinel after its value is taken.
+            let result = next.deref().data.assume_init_read();
+
+            // Concurrent operations may still read the old sentinel, so it's retired instead of
+            // freed right away.
+            sentinel_ref.tracker.retire();
+            R::retire(self.sentinel, self.guard);
+            self.sentinel = next;
+            Some(result)
+        }
+    }
+}
+
+impl<'g, T, R: Reclaimer> Drop for Drain<'g, T, R> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+        // SAFETY: `last` is not accessed anymore.
+        unsafe { R::release(self.last, self.guard) };
+    }
+}
+
+impl<'g, T, R: Reclaimer> fmt::Debug for Drain<'g, T, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Drain")
+            .field("sentinel", &self.sentinel)
+            .field("last", &self.last)
+            .finish()
     }
 }
 
-impl<T> Drop for Queue<T> {
+impl<T, R: Reclaimer> Drop for Queue<T, R> {
     fn drop(&mut self) {
         // Destroy the sentinel node.
 
@@ -427,4 +623,181 @@ mod test {
         assert!(!q.is_empty());
         assert!(q.try_pop().is_some());
     }
+
+    #[test]
+    fn drain() {
+        const THREADS: usize = 4;
+        const COUNT: usize = 10_000;
+        let q = super::Queue::new();
+        let guard = &pin();
+
+        for i in 0..10 {
+            q.push(i, guard);
+        }
+        assert!(q.drain(guard).take(5).eq(0..5));
+        assert!(q.try_pop(guard).is_none());
+        q.push(10, guard);
+        q.clear(guard);
+        assert!(q.try_pop(guard).is_none());
+
+        // Every pushed value is either popped or drained exactly once, and the values drained at
+        // once are in FIFO order for each pusher.
+        let mut taken = scope(|scope| {
+            let pushers = (0..THREADS)
+                .map(|t| {
+                    let q = &q;
+                    scope.spawn(move || {
+                        let mut popped = Vec::new();
+                        for i in 0..COUNT {
+                            let guard = &pin();
+                            q.push(t * COUNT + i, guard);
+                            if i % 2 == 0 {
+                                popped.extend(q.try_pop(guard));
+                            }
+                        }
+                        popped
+                    })
+                })
+                .collect::<Vec<_>>();
+            let drainer = scope.spawn(|| {
+                let mut drained = Vec::new();
+                for _ in 0..COUNT {
+                    let mut last = [None; THREADS];
+                    for v in q.drain(&pin()) {
+                        assert!(last[v / COUNT] < Some(v));
+                        last[v / COUNT] = Some(v);
+                        drained.push(v);
+                    }
+                }
+                drained
+            });
+            let mut taken = drainer.join().unwrap();
+            for pusher in pushers {
+                taken.extend(pusher.join().unwrap());
+            }
+            taken
+        });
+        taken.extend(q.drain(guard));
+
+        taken.sort_unstable();
+        assert_eq!(taken, (0..THREADS * COUNT).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn linearizable() {
+        use crate::lockfree::linearizability::{check, record, Spec};
+        use std::collections::VecDeque;
+
+        #[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+        struct Fifo(VecDeque<u64>);
+
+        #[derive(Debug)]
+        enum Op {
+            Push(u64),
+            Pop,
+        }
+
+        impl Spec for Fifo {
+            type Op = Op;
+            type Ret = Option<u64>;
+
+            fn apply(&mut self, op: &Op) -> Option<u64> {
+                match *op {
+                    Op::Push(v) => {
+                        self.0.push_back(v);
+                        None
+                    }
+                    Op::Pop => self.0.pop_front(),
+                }
+            }
+        }
+
+        for seed in 0..100 {
+            let q = super::Queue::new();
+            let history = record(
+                4,
+                16,
+                seed,
+                |_, rng| match rng.next() % 2 {
+                    0 => Op::Push(rng.next() % 8),
+                    _ => Op::Pop,
+                },
+                |op| {
+                    let guard = &pin();
+                    match *op {
+                        Op::Push(v) => {
+                            q.push(v, guard);
+                            None
+                        }
+                        Op::Pop => q.try_pop(guard),
+                    }
+                },
+            );
+            assert!(check(Fifo::default(), &history), "{history:#?}");
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats() {
+        const COUNT: usize = 1_000;
+        let q = super::Queue::new();
+        let counters = q.counters.clone();
+
+        // The guard is held throughout, so no retired node is freed and the counts are exact.
+        let guard = pin();
+
+        // A pop retires the old sentinel, and the popped node becomes the sentinel. The initial
+        // sentinel is not counted.
+        for i in 0..COUNT {
+            q.push(i, &guard);
+            assert_eq!(q.try_pop(&guard), Some(i));
+        }
+        assert_eq!(q.try_pop(&guard), None);
+        assert_eq!(
+            counters.stats(),
+            Stats {
+                allocated: COUNT,
+                retired: COUNT - 1,
+                freed: 0
+            }
+        );
+
+        // A push that linked its node but has not swung the tail yet leaves the tail at the
+        // sentinel. The pop swings the tail before it retires the sentinel, so the tail never
+        // points to a retired node.
+        let sentinel = q.head.load(Ordering::Relaxed, &guard);
+        q.push(COUNT, &guard);
+        q.tail.store(sentinel, Ordering::Relaxed);
+        assert_eq!(q.try_pop(&guard), Some(COUNT));
+        assert_ne!(q.tail.load(Ordering::Relaxed, &guard), sentinel);
+        assert_eq!(
+            q.tail.load(Ordering::Relaxed, &guard),
+            q.head.load(Ordering::Relaxed, &guard)
+        );
+        assert_eq!(counters.stats().retired, COUNT);
+
+        // A drain retires the old sentinel and every detached node but the last, which becomes
+        // the sentinel, even if the values are not yielded.
+        for i in 0..COUNT {
+            q.push(i, &guard);
+        }
+        assert_eq!(q.drain(&guard).take(1).collect::<Vec<_>>(), [0]);
+        assert!(q.try_pop(&guard).is_none());
+        assert_eq!(counters.stats().retired, 2 * COUNT);
+
+        // Dropping the queue frees the sentinel and the nodes still in it right away, without
+        // retiring them.
+        q.push(0, &guard);
+        drop(q);
+        assert_eq!(
+            counters.stats(),
+            Stats {
+                allocated: 2 * COUNT + 2,
+                retired: 2 * COUNT,
+                freed: 2
+            }
+        );
+        drop(guard);
+    }
 }
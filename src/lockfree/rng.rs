@@ -0,0 +1,20 @@
+//! Xorshift pseudo-random number generator, to avoid depending on `rand`.
+
+/// Xorshift pseudo-random number generator.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    /// Creates a generator from the given seed. Any seed, including 0, is valid.
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    /// Returns the next pseudo-random number.
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
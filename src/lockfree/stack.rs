@@ -1,31 +1,211 @@
+use core::cell::Cell;
+use core::fmt;
+use core::marker::PhantomData;
 use core::mem::{self, ManuallyDrop};
 use core::ptr;
-use core::sync::atomic::Ordering;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 use crossbeam_epoch::{Atomic, Owned, Shared};
+use crossbeam_utils::{Backoff, CachePadded};
+
+use super::rng::Rng;
+#[cfg(feature = "stats")]
+use super::stats::Stats;
+use super::stats::{Counters, Tracker};
+use crate::reclaim::{Epoch, Reclaimer};
+
+/// Number of slots in the elimination array.
+const ELIM_SIZE: usize = 16;
 
 /// Treiber's lock-free stack.
 ///
 /// Usable with any number of producers and consumers.
+///
+/// A stack created with [`Stack::with_elimination`] backs off to an elimination array when a CAS
+/// on the head fails. There, a push and a pop can exchange the value with each other without
+/// touching the head at all.
+///
+/// Hendler, Shavit and Yerushalmi.  A Scalable Lock-free Stack Algorithm.  SPAA 2004.
+/// <https://dl.acm.org/doi/10.1145/1007912.1007944>
+///
+/// The nodes are reclaimed with the memory reclamation scheme `R`.
 #[derive(Debug)]
-pub struct Stack<T> {
+pub struct Stack<T, R: Reclaimer = Epoch> {
     head: Atomic<Node<T>>,
+    elimination: Option<Box<Elimination<T>>>,
+    counters: Counters,
+    _marker: PhantomData<R>,
+}
+
+/// Iterator over the values taken from a [`Stack`] by [`Stack::drain`], from top to bottom.
+///
+/// The values that are not yielded are dropped when the iterator is dropped.
+pub struct Drain<'g, T, R: Reclaimer = Epoch> {
+    next: Shared<'g, Node<T>>,
+    guard: &'g R::Guard,
+}
+
+/// Elimination array.
+///
+/// A push offers its node in a slot and waits for a pop to take it. The number of slots in use
+/// adapts to the contention: it grows when a push finds its slot occupied, and shrinks when a push
+/// times out without meeting a pop.
+#[derive(Debug)]
+struct Elimination<T> {
+    slots: [CachePadded<Atomic<Node<T>>>; ELIM_SIZE],
+    width: AtomicUsize,
 }
 
 #[derive(Debug)]
 struct Node<T> {
     data: ManuallyDrop<T>,
     next: *const Node<T>,
+    tracker: Tracker,
 }
 
 // Any particular `T` should never be accessed concurrently, so no need for `Sync`.
-unsafe impl<T: Send> Send for Stack<T> {}
-unsafe impl<T: Send> Sync for Stack<T> {}
+unsafe impl<T: Send, R: Reclaimer> Send for Stack<T, R> {}
+unsafe impl<T: Send, R: Reclaimer> Sync for Stack<T, R> {}
 
-impl<T> Default for Stack<T> {
+impl<T, R: Reclaimer> Default for Stack<T, R> {
     fn default() -> Self {
         Self {
             head: Atomic::null(),
+            elimination: None,
+            counters: Counters::default(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Returns a random index less than `width`.
+fn random_index(width: usize) -> usize {
+    thread_local! {
+        static RNG: Cell<Option<Rng>> = Cell::new(None);
+    }
+
+    RNG.with(|rng| {
+        // Seeded by the address of the thread-local state.
+        let mut state = rng
+            .get()
+            .unwrap_or_else(|| Rng::new(rng as *const Cell<Option<Rng>> as usize as u64));
+        let x = state.next();
+        rng.set(Some(state));
+        x as usize % width
+    })
+}
+
+impl<T> Elimination<T> {
+    /// Offers `n` to a pop, and waits for a while.
+    ///
+    /// Returns `Err(n)` if no pop took it.
+    fn try_push<R: Reclaimer>(
+        &self,
+        n: Owned<Node<T>>,
+        guard: &R::Guard,
+    ) -> Result<(), Owned<Node<T>>> {
+        let width = self.width.load(Ordering::Relaxed);
+        let slot = &self.slots[random_index(width)];
+
+        // `n` is protected so that it is not freed while we compare against it below.
+        let n = R::protect_owned(n, guard);
+        let atomic_guard = R::atomic_guard(guard);
+
+        // Release: the pop that takes `n` should see its data.
+        match slot.compare_exchange(
+            Shared::null(),
+            n,
+            Ordering::Release,
+            Ordering::Relaxed,
+            atomic_guard,
+        ) {
+            Ok(_) => {}
+            Err(_) => {
+                // Collided with another push, so spread out.
+                if width < ELIM_SIZE {
+                    let _ = self.width.compare_exchange(
+                        width,
+                        width + 1,
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    );
+                }
+                // SAFETY: the CAS failed, so we still have the sole ownership of `n`.
+                let n_owned = unsafe { n.into_owned() };
+                unsafe { R::release(n, guard) };
+                return Err(n_owned);
+            }
+        }
+
+        let backoff = Backoff::new();
+        while !backoff.is_completed() && slot.load(Ordering::Relaxed, atomic_guard) == n {
+            backoff.snooze();
+        }
+
+        // Withdraw the offer. `n` is protected, so if the CAS fails, the slot holds another node
+        // and `n` was taken by a pop.
+        if slot
+            .compare_exchange(
+                n,
+                Shared::null(),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+                atomic_guard,
+            )
+            .is_err()
+        {
+            // SAFETY: we no longer access `n`.
+            unsafe { R::release(n, guard) };
+            return Ok(());
+        }
+
+        // Timed out, so concentrate.
+        if width > 1 {
+            let _ =
+                self.width
+                    .compare_exchange(width, width - 1, Ordering::Relaxed, Ordering::Relaxed);
+        }
+        // SAFETY: we withdrew `n`, so we have its sole ownership again.
+        let n_owned = unsafe { n.into_owned() };
+        unsafe { R::release(n, guard) };
+        Err(n_owned)
+    }
+
+    /// Takes a value offered by a push, if any.
+    fn try_pop<R: Reclaimer>(&self, guard: &R::Guard) -> Option<T> {
+        let width = self.width.load(Ordering::Relaxed);
+        let slot = &self.slots[random_index(width)];
+
+        let n = R::protect(slot, Ordering::Relaxed, guard);
+        if n.is_null() {
+            return None;
+        }
+
+        // Acquire: to see the data of `n`.
+        if slot
+            .compare_exchange(
+                n,
+                Shared::null(),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+                R::atomic_guard(guard),
+            )
+            .is_err()
+        {
+            // SAFETY: we no longer access `n`.
+            unsafe { R::release(n, guard) };
+            return None;
+        }
+
+        // SAFETY: we took `n` from the slot, so we own its data. The push that offered `n` may
+        // still compare against it, so it's retired instead of freed right away.
+        unsafe {
+            let n_ref = n.deref();
+            let result = ManuallyDrop::into_inner(ptr::read(&n_ref.data));
+            n_ref.tracker.retire();
+            R::retire(n, guard);
+            R::release(n, guard);
+            Some(result)
         }
     }
 }
@@ -36,26 +216,61 @@ impl<T> Stack<T> {
         Self::default()
     }
 
+    /// Creates a new, empty stack with an elimination array.
+    pub fn with_elimination() -> Stack<T> {
+        Self::with_elimination_in()
+    }
+}
+
+impl<T, R: Reclaimer> Stack<T, R> {
+    /// Creates a new, empty stack with an elimination array, whose nodes are reclaimed with `R`.
+    pub fn with_elimination_in() -> Self {
+        Self {
+            head: Atomic::null(),
+            elimination: Some(Box::new(Elimination {
+                slots: Default::default(),
+                width: AtomicUsize::new(1),
+            })),
+            counters: Counters::default(),
+            _marker: PhantomData,
+        }
+    }
+
     /// Pushes a value on top of the stack.
     pub fn push(&self, t: T) {
         let mut n = Owned::new(Node {
             data: ManuallyDrop::new(t),
             next: ptr::null(),
+            tracker: self.counters.track(),
         });
 
-        let guard = crossbeam_epoch::pin();
+        let guard = R::pin();
+        let atomic_guard = R::atomic_guard(&guard);
+        let backoff = Backoff::new();
 
         loop {
-            let head = self.head.load(Ordering::Relaxed, &guard);
+            // `head` is not dereferenced, so it need not be protected.
+            let head = self.head.load(Ordering::Relaxed, atomic_guard);
             n.next = head.as_raw();
 
-            match self
-                .head
-                .compare_exchange(head, n, Ordering::Release, Ordering::Relaxed, &guard)
-            {
+            match self.head.compare_exchange(
+                head,
+                n,
+                Ordering::Release,
+                Ordering::Relaxed,
+                atomic_guard,
+            ) {
                 Ok(_) => break,
                 Err(e) => n = e.new,
             }
+
+            if let Some(elimination) = &self.elimination {
+                match elimination.try_push::<R>(n, &guard) {
+                    Ok(()) => break,
+                    Err(e) => n = e,
+                }
+                backoff.spin();
+            }
         }
     }
 
@@ -63,15 +278,22 @@ impl<T> Stack<T> {
     ///
     /// Returns `None` if the stack is empty.
     pub fn pop(&self) -> Option<T> {
-        let guard = crossbeam_epoch::pin();
+        let guard = R::pin();
+        let backoff = Backoff::new();
         loop {
-            let head = self.head.load(Ordering::Acquire, &guard);
+            let head = R::protect(&self.head, Ordering::Acquire, &guard);
             let h = unsafe { head.as_ref() }?;
             let next = Shared::from(h.next);
 
             if self
                 .head
-                .compare_exchange(head, next, Ordering::Relaxed, Ordering::Relaxed, &guard)
+                .compare_exchange(
+                    head,
+                    next,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                    R::atomic_guard(&guard),
+                )
                 .is_ok()
             {
                 // Since the above `compare_exchange()` succeeded, `head` is detached from `self` so
@@ -83,21 +305,92 @@ impl<T> Stack<T> {
                 let result = ManuallyDrop::into_inner(unsafe { ptr::read(&h.data) });
 
                 // SAFETY: `head` is unreachable, and we no longer access `head`.
-                unsafe { guard.defer_destroy(head) };
+                h.tracker.retire();
+                unsafe { R::retire(head, &guard) };
 
                 return Some(result);
             }
+
+            // SAFETY: we no longer access `head`.
+            unsafe { R::release(head, &guard) };
+
+            if let Some(elimination) = &self.elimination {
+                if let Some(result) = elimination.try_pop::<R>(&guard) {
+                    return Some(result);
+                }
+                backoff.spin();
+            }
         }
     }
 
+    /// Takes all the elements from the stack at once.
+    ///
+    /// The stack is detached in a single swap of its head, so the returned iterator yields the
+    /// elements that were in the stack at that moment, even if other threads concurrently push and
+    /// pop.
+    pub fn drain<'g>(&self, guard: &'g R::Guard) -> Drain<'g, T, R> {
+        // Acquire: to see the data of the detached nodes.
+        let next = self
+            .head
+            .swap(Shared::null(), Ordering::Acquire, R::atomic_guard(guard));
+        Drain { next, guard }
+    }
+
+    /// Removes all the elements from the stack at once.
+    ///
+    /// See [`Stack::drain`].
+    pub fn clear(&self, guard: &R::Guard) {
+        self.drain(guard).for_each(drop);
+    }
+
     /// Returns `true` if the stack is empty.
     pub fn is_empty(&self) -> bool {
-        let guard = crossbeam_epoch::pin();
-        self.head.load(Ordering::Acquire, &guard).is_null()
+        let guard = R::pin();
+        self.head
+            .load(Ordering::Acquire, R::atomic_guard(&guard))
+            .is_null()
+    }
+
+    /// Returns the memory reclamation statistics of the stack.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.counters.stats()
+    }
+}
+
+impl<'g, T, R: Reclaimer> Iterator for Drain<'g, T, R> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let node = self.next;
+        // SAFETY: the detached nodes are retired only by this iterator, so they are not freed yet.
+        let node_ref = unsafe { node.as_ref() }?;
+        self.next = Shared::from(node_ref.next);
+
+        // SAFETY: no other thread has access to `data` after the nodes are detached. Concurrent
+        // pops may still read `next` of the nodes, so they are retired instead of freed right away.
+        unsafe {
+            let result = ManuallyDrop::into_inner(ptr::read(&node_ref.data));
+            node_ref.tracker.retire();
+            R::retire(node, self.guard);
+            Some(result)
+        }
     }
 }
 
-impl<T> Drop for Stack<T> {
+impl<'g, T, R: Reclaimer> Drop for Drain<'g, T, R> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+impl<'g, T, R: Reclaimer> fmt::Debug for Drain<'g, T, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Drain").field("next", &self.next).finish()
+    }
+}
+
+impl<T, R: Reclaimer> Drop for Stack<T, R> {
     fn drop(&mut self) {
         let mut o_curr = mem::take(&mut self.head);
 
@@ -131,4 +424,207 @@ mod test {
 
         assert!(stack.pop().is_none());
     }
+
+    #[test]
+    fn elimination() {
+        const THREADS: usize = 8;
+        const COUNT: usize = 10_000;
+        let stack = Stack::with_elimination();
+
+        let popped = scope(|scope| {
+            let handles = (0..THREADS)
+                .map(|t| {
+                    let stack = &stack;
+                    scope.spawn(move || {
+                        let mut popped = Vec::new();
+                        for i in 0..COUNT {
+                            stack.push(t * COUNT + i);
+                            popped.extend(stack.pop());
+                        }
+                        popped
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        assert!(stack.is_empty());
+        let mut popped = popped;
+        popped.sort_unstable();
+        assert_eq!(popped, (0..THREADS * COUNT).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn drain() {
+        const THREADS: usize = 4;
+        const COUNT: usize = 10_000;
+        let stack = Stack::new();
+        let guard = &crossbeam_epoch::pin();
+
+        for i in 0..10 {
+            stack.push(i);
+        }
+        assert!(stack.drain(guard).take(5).eq((5..10).rev()));
+        assert!(stack.is_empty());
+        stack.push(10);
+        stack.clear(guard);
+        assert!(stack.is_empty());
+
+        // Every pushed value is either popped or drained exactly once.
+        let mut taken = scope(|scope| {
+            let pushers = (0..THREADS)
+                .map(|t| {
+                    let stack = &stack;
+                    scope.spawn(move || {
+                        let mut popped = Vec::new();
+                        for i in 0..COUNT {
+                            stack.push(t * COUNT + i);
+                            if i % 2 == 0 {
+                                popped.extend(stack.pop());
+                            }
+                        }
+                        popped
+                    })
+                })
+                .collect::<Vec<_>>();
+            let drainer = scope.spawn(|| {
+                let mut drained = Vec::new();
+                for _ in 0..COUNT {
+                    drained.extend(stack.drain(&crossbeam_epoch::pin()));
+                }
+                drained
+            });
+            let mut taken = drainer.join().unwrap();
+            for pusher in pushers {
+                taken.extend(pusher.join().unwrap());
+            }
+            taken
+        });
+        taken.extend(stack.drain(guard));
+
+        taken.sort_unstable();
+        assert_eq!(taken, (0..THREADS * COUNT).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn linearizable() {
+        use crate::lockfree::linearizability::{check, record, Spec};
+
+        #[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+        struct Lifo(Vec<u64>);
+
+        #[derive(Debug)]
+        enum Op {
+            Push(u64),
+            Pop,
+        }
+
+        impl Spec for Lifo {
+            type Op = Op;
+            type Ret = Option<u64>;
+
+            fn apply(&mut self, op: &Op) -> Option<u64> {
+                match *op {
+                    Op::Push(v) => {
+                        self.0.push(v);
+                        None
+                    }
+                    Op::Pop => self.0.pop(),
+                }
+            }
+        }
+
+        for seed in 0..100 {
+            for stack in [Stack::new(), Stack::with_elimination()] {
+                let history = record(
+                    4,
+                    16,
+                    seed,
+                    |_, rng| match rng.next() % 2 {
+                        0 => Op::Push(rng.next() % 8),
+                        _ => Op::Pop,
+                    },
+                    |op| match *op {
+                        Op::Push(v) => {
+                            stack.push(v);
+                            None
+                        }
+                        Op::Pop => stack.pop(),
+                    },
+                );
+                assert!(check(Lifo::default(), &history), "{history:#?}");
+            }
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats() {
+        const COUNT: usize = 1_000;
+        let stack = Stack::with_elimination();
+        let counters = stack.counters.clone();
+
+        // The guard is held throughout, so no retired node is freed and the counts are exact.
+        let guard = crossbeam_epoch::pin();
+
+        // A pop retires the node it detaches from the head.
+        for i in 0..COUNT {
+            stack.push(i);
+        }
+        for _ in 0..COUNT / 2 {
+            assert!(stack.pop().is_some());
+        }
+        assert_eq!(
+            counters.stats(),
+            Stats {
+                allocated: COUNT,
+                retired: COUNT / 2,
+                freed: 0
+            }
+        );
+
+        // A pop that takes the node offered by a push in the elimination array retires it without
+        // touching the head. With a width of 1, the push and the pop meet in the first slot.
+        let elimination = stack.elimination.as_ref().unwrap();
+        assert_eq!(elimination.width.load(Ordering::Relaxed), 1);
+        let offered = Owned::new(Node {
+            data: ManuallyDrop::new(usize::MAX),
+            next: ptr::null(),
+            tracker: stack.counters.track(),
+        });
+        elimination.slots[0].store(offered, Ordering::Release);
+        assert_eq!(elimination.try_pop::<Epoch>(&guard), Some(usize::MAX));
+        assert_eq!(elimination.try_pop::<Epoch>(&guard), None);
+        assert_eq!(
+            counters.stats(),
+            Stats {
+                allocated: COUNT + 1,
+                retired: COUNT / 2 + 1,
+                freed: 0
+            }
+        );
+
+        // A drain retires all the detached nodes, even the ones it does not yield.
+        assert_eq!(stack.drain(&guard).take(1).count(), 1);
+        assert!(stack.is_empty());
+        assert_eq!(counters.stats().retired, COUNT + 1);
+
+        // Dropping the stack frees the nodes still in it right away, without retiring them.
+        for i in 0..COUNT {
+            stack.push(i);
+        }
+        drop(stack);
+        assert_eq!(
+            counters.stats(),
+            Stats {
+                allocated: 2 * COUNT + 1,
+                retired: COUNT + 1,
+                freed: COUNT
+            }
+        );
+        drop(guard);
+    }
 }
@@ -0,0 +1,123 @@
+//! Memory reclamation statistics, enabled with the `stats` feature.
+//!
+//! Each node carries a [`Tracker`] that shares the counters of its container. The tracker counts
+//! the node when it is allocated and retired, and its `Drop` counts the node when it is actually
+//! freed. Since retired nodes may be freed after the container is gone, the counters are
+//! reference-counted.
+
+#[cfg(feature = "stats")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "stats")]
+use std::sync::Arc;
+
+/// A snapshot of the memory reclamation statistics of a container.
+///
+/// `retired - freed` is the number of nodes that are removed from the container but not freed
+/// yet, e.g. because a long-lived `Guard` keeps the epoch from advancing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// Number of allocated nodes.
+    pub allocated: usize,
+    /// Number of retired nodes, i.e. `defer_destroy()` calls.
+    pub retired: usize,
+    /// Number of freed nodes, including the nodes freed without being retired, e.g. the nodes
+    /// freed when the container is dropped.
+    pub freed: usize,
+}
+
+/// Counters shared by a container and its nodes.
+#[cfg(feature = "stats")]
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Counters(Arc<Inner>);
+
+#[cfg(feature = "stats")]
+#[derive(Debug, Default)]
+struct Inner {
+    allocated: AtomicUsize,
+    retired: AtomicUsize,
+    freed: AtomicUsize,
+}
+
+/// Counters shared by a container and its nodes.
+#[cfg(not(feature = "stats"))]
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Counters {}
+
+/// Tracks a node with the counters of its container.
+#[cfg(feature = "stats")]
+#[derive(Debug)]
+pub(crate) struct Tracker(Option<Counters>);
+
+/// Tracks a node with the counters of its container.
+#[cfg(not(feature = "stats"))]
+#[derive(Debug)]
+pub(crate) struct Tracker {}
+
+#[cfg(feature = "stats")]
+impl Counters {
+    /// Counts a newly allocated node.
+    #[inline]
+    pub(crate) fn track(&self) -> Tracker {
+        let _ = self.0.allocated.fetch_add(1, Ordering::Relaxed);
+        Tracker(Some(self.clone()))
+    }
+
+    /// Returns the current statistics.
+    pub(crate) fn stats(&self) -> Stats {
+        Stats {
+            allocated: self.0.allocated.load(Ordering::Relaxed),
+            retired: self.0.retired.load(Ordering::Relaxed),
+            freed: self.0.freed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(not(feature = "stats"))]
+impl Counters {
+    /// Counts a newly allocated node.
+    #[inline]
+    pub(crate) fn track(&self) -> Tracker {
+        Tracker {}
+    }
+}
+
+#[cfg(feature = "stats")]
+impl Tracker {
+    /// A tracker of a node that is not counted, e.g. a sentinel or a node made outside of the
+    /// container.
+    #[inline]
+    pub(crate) const fn untracked() -> Self {
+        Self(None)
+    }
+
+    /// Counts the retirement of the node.
+    #[inline]
+    pub(crate) fn retire(&self) {
+        if let Some(counters) = &self.0 {
+            let _ = counters.0.retired.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(not(feature = "stats"))]
+impl Tracker {
+    /// A tracker of a node that is not counted, e.g. a sentinel or a node made outside of the
+    /// container.
+    #[inline]
+    pub(crate) const fn untracked() -> Self {
+        Self {}
+    }
+
+    /// Counts the retirement of the node.
+    #[inline]
+    pub(crate) fn retire(&self) {}
+}
+
+#[cfg(feature = "stats")]
+impl Drop for Tracker {
+    fn drop(&mut self) {
+        if let Some(counters) = &self.0 {
+            let _ = counters.0.freed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
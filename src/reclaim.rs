@@ -0,0 +1,101 @@
+//! Memory reclamation schemes.
+//!
+//! The lock-free data structures in [`crate::lockfree`] are written against the [`Reclaimer`]
+//! trait, so that the same code can be run with different schemes, e.g. epoch-based reclamation
+//! ([`Epoch`]) or hazard pointers.
+
+use core::sync::atomic::Ordering;
+
+use crossbeam_epoch::{Atomic, Guard, Owned, Shared};
+
+/// A memory reclamation scheme for the nodes pointed to by [`Atomic`]s.
+///
+/// A *guard* is a scope of protection: the pointers protected with a guard are not freed until the
+/// guard is dropped. For epoch-based reclamation, a guard is a pinned epoch and protects every
+/// pointer. For hazard pointers, a guard owns a set of hazard slots, one for each protected
+/// pointer. The data structures end the protection of the pointers they no longer need with
+/// [`Reclaimer::release`], so that the slots are reused.
+///
+/// # Safety
+///
+/// A pointer returned by [`Reclaimer::protect`] or [`Reclaimer::protect_owned`] must not be freed
+/// until the guard is dropped or the protection is released with [`Reclaimer::release`], provided
+/// that it was not retired when it was protected.
+pub unsafe trait Reclaimer {
+    /// Scope of protection.
+    type Guard;
+
+    /// Creates a new guard.
+    fn pin() -> Self::Guard;
+
+    /// Loads a pointer from `src` and protects it.
+    ///
+    /// The pointer is protected only if "`src` still points to it" implies that it is not retired.
+    /// For example, this is not the case if `src` is in a node that is already removed from the
+    /// data structure.
+    fn protect<'g, T>(src: &Atomic<T>, order: Ordering, guard: &'g Self::Guard) -> Shared<'g, T>;
+
+    /// Protects a node that is not shared with other threads yet.
+    fn protect_owned<T>(owned: Owned<T>, guard: &Self::Guard) -> Shared<'_, T>;
+
+    /// Ends a protection of `ptr` with `guard`, so that the scheme can reuse it for other pointers.
+    /// If `ptr` is protected with `guard` more than once, the other protections are kept.
+    ///
+    /// Does nothing by default, e.g. for epoch-based reclamation, which protects every pointer.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` and the references obtained from it must not be used afterwards, unless `ptr` is
+    /// protected again.
+    #[inline]
+    unsafe fn release<T>(ptr: Shared<'_, T>, guard: &Self::Guard) {
+        let _ = (ptr, guard);
+    }
+
+    /// Returns a guard for the operations on [`Atomic`] other than loading a pointer to
+    /// dereference, e.g. `compare_exchange()`.
+    ///
+    /// The pointers obtained with the returned guard are not necessarily protected.
+    fn atomic_guard(guard: &Self::Guard) -> &Guard;
+
+    /// Retires a pointer, which is freed once it is not protected anymore.
+    ///
+    /// # Safety
+    ///
+    /// * `ptr` must be removed from shared memory, and must be created by `Owned::new()`.
+    /// * The same `ptr` should only be retired once.
+    /// * `ptr` may be freed by another thread, so it must be safe to drop `T` on any thread.
+    unsafe fn retire<T>(ptr: Shared<'_, T>, guard: &Self::Guard);
+}
+
+/// Epoch-based reclamation with [`crossbeam_epoch`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Epoch;
+
+unsafe impl Reclaimer for Epoch {
+    type Guard = Guard;
+
+    fn pin() -> Guard {
+        crossbeam_epoch::pin()
+    }
+
+    #[inline]
+    fn protect<'g, T>(src: &Atomic<T>, order: Ordering, guard: &'g Guard) -> Shared<'g, T> {
+        src.load(order, guard)
+    }
+
+    #[inline]
+    fn protect_owned<T>(owned: Owned<T>, guard: &Guard) -> Shared<'_, T> {
+        owned.into_shared(guard)
+    }
+
+    #[inline]
+    fn atomic_guard(guard: &Guard) -> &Guard {
+        guard
+    }
+
+    #[inline]
+    unsafe fn retire<T>(ptr: Shared<'_, T>, guard: &Guard) {
+        guard.defer_destroy(ptr)
+    }
+}